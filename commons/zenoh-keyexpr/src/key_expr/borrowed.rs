@@ -11,7 +11,10 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
-use super::{canon::Canonizable, OwnedKeyExpr, FORBIDDEN_CHARS};
+use super::{
+    canon::{Canonizable, Normalization},
+    OwnedKeyExpr, FORBIDDEN_CHARS,
+};
 // use crate::core::WireExpr;
 use alloc::{
     borrow::{Borrow, ToOwned},
@@ -76,6 +79,16 @@ impl keyexpr {
         Self::new(t)
     }
 
+    /// Canonizes the passed string, returning the resulting `OwnedKeyExpr` along with the list of
+    /// the rewrite rules that were applied to it, which is empty if the passed string was already canon.
+    ///
+    /// Will return Err if the passed string isn't a valid key expression despite canonization.
+    pub fn canonicalize_verbose(s: &str) -> ZResult<(OwnedKeyExpr, Vec<Normalization>)> {
+        let mut s = String::from(s);
+        let normalizations = super::canon::canonize_verbose(&mut s);
+        Ok((OwnedKeyExpr::new(s)?, normalizations))
+    }
+
     /// Returns `true` if the `keyexpr`s intersect, i.e. there exists at least one key which is contained in both of the sets defined by `self` and `other`.
     pub fn intersects(&self, other: &Self) -> bool {
         use super::intersect::Intersector;
@@ -88,6 +101,12 @@ impl keyexpr {
         super::include::DEFAULT_INCLUDER.includes(self, other)
     }
 
+    /// Returns `true` if `self` strictly includes `other`, i.e. `self` [includes](keyexpr::includes) `other`
+    /// and contains at least one key which doesn't belong to the set defined by `other`.
+    pub fn strictly_includes(&self, other: &Self) -> bool {
+        self.includes(other) && !other.includes(self)
+    }
+
     /// Returns the relation between `self` and `other` from `self`'s point of view ([`SetIntersectionLevel::Includes`] signifies that `self` includes `other`).
     ///
     /// Note that this is slower than [`keyexpr::intersects`] and [`keyexpr::includes`], so you should favor these methods for most applications.
@@ -15,7 +15,7 @@ use crate::key_expr::{
     utils::{Split, Writer},
     DELIMITER, DOUBLE_WILD, SINGLE_WILD,
 };
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use core::{slice, str};
 
 pub trait Canonizable {
@@ -26,93 +26,118 @@ const DOLLAR_STAR: &[u8; 2] = b"$*";
 
 impl Canonizable for &mut str {
     fn canonize(&mut self) {
-        let mut writer = Writer {
-            ptr: self.as_mut_ptr(),
-            len: 0,
-        };
-        if let Some(position) = self.find("$*$*") {
-            writer.len = position;
-            let mut need_final_write = true;
-            for between_dollarstar in self.as_bytes()[(position + 4)..].splitter(DOLLAR_STAR) {
-                need_final_write = between_dollarstar.is_empty();
-                if !need_final_write {
-                    writer.write(DOLLAR_STAR.as_ref());
-                    writer.write(between_dollarstar);
-                }
-            }
-            if need_final_write {
-                writer.write(DOLLAR_STAR.as_ref())
-            }
-            *self = unsafe {
-                str::from_utf8_unchecked_mut(slice::from_raw_parts_mut(writer.ptr, writer.len))
+        canonize_reporting(self, |_| {})
+    }
+}
+
+// Canonizes `s` in place, calling `report` each time one of the rewrite rules is applied.
+fn canonize_reporting<F: FnMut(Normalization)>(s: &mut &mut str, mut report: F) {
+    let mut writer = Writer {
+        ptr: s.as_mut_ptr(),
+        len: 0,
+    };
+    if let Some(position) = s.find("$*$*") {
+        report(Normalization::CollapsedDollarStars);
+        writer.len = position;
+        let mut need_final_write = true;
+        for between_dollarstar in s.as_bytes()[(position + 4)..].splitter(DOLLAR_STAR) {
+            need_final_write = between_dollarstar.is_empty();
+            if !need_final_write {
+                writer.write(DOLLAR_STAR.as_ref());
+                writer.write(between_dollarstar);
             }
         }
-        writer.len = 0;
-        let mut ke = self.as_bytes().splitter(&b'/');
-        let mut in_big_wild = false;
+        if need_final_write {
+            writer.write(DOLLAR_STAR.as_ref())
+        }
+        *s = unsafe {
+            str::from_utf8_unchecked_mut(slice::from_raw_parts_mut(writer.ptr, writer.len))
+        }
+    }
+    writer.len = 0;
+    let mut ke = s.as_bytes().splitter(&b'/');
+    let mut in_big_wild = false;
 
-        for chunk in ke.by_ref() {
-            if chunk.is_empty() {
-                break;
-            }
-            if in_big_wild {
-                match chunk {
-                    [SINGLE_WILD] | b"$*" => {
-                        writer.write_byte(b'*');
-                        break;
-                    }
-                    DOUBLE_WILD => continue,
-                    _ => {
-                        writer.write(b"**/");
-                        writer.write(chunk);
-                        in_big_wild = false;
-                        break;
-                    }
-                }
-            } else if chunk == DOUBLE_WILD {
-                in_big_wild = true;
-                continue;
-            } else {
-                writer.write(if chunk == b"$*" { b"*" } else { chunk });
-                break;
-            }
+    for chunk in ke.by_ref() {
+        if chunk.is_empty() {
+            break;
         }
-        for chunk in ke {
-            if chunk.is_empty() {
-                writer.write_byte(b'/');
-                continue;
-            }
-            if in_big_wild {
-                match chunk {
-                    [SINGLE_WILD] | b"$*" => {
-                        writer.write(b"/*");
-                    }
-                    DOUBLE_WILD => {}
-                    _ => {
-                        writer.write(b"/**/");
-                        writer.write(chunk);
-                        in_big_wild = false;
-                    }
+        if in_big_wild {
+            match chunk {
+                [SINGLE_WILD] | b"$*" => {
+                    report_wild(chunk, &mut report);
+                    writer.write_byte(b'*');
+                    break;
+                }
+                DOUBLE_WILD => {
+                    report(Normalization::CollapsedDoubleWilds);
+                    continue;
+                }
+                _ => {
+                    writer.write(b"**/");
+                    writer.write(chunk);
+                    in_big_wild = false;
+                    break;
                 }
-            } else if chunk == DOUBLE_WILD {
-                in_big_wild = true;
-            } else {
-                writer.write_byte(DELIMITER);
-                writer.write(if chunk == b"$*" { b"*" } else { chunk });
             }
+        } else if chunk == DOUBLE_WILD {
+            in_big_wild = true;
+            continue;
+        } else {
+            writer.write(dollar_star_chunk(chunk, &mut report));
+            break;
+        }
+    }
+    for chunk in ke {
+        if chunk.is_empty() {
+            writer.write_byte(b'/');
+            continue;
         }
         if in_big_wild {
-            if writer.len != 0 {
-                writer.write_byte(DELIMITER);
+            match chunk {
+                [SINGLE_WILD] | b"$*" => {
+                    report_wild(chunk, &mut report);
+                    writer.write(b"/*");
+                }
+                DOUBLE_WILD => report(Normalization::CollapsedDoubleWilds),
+                _ => {
+                    writer.write(b"/**/");
+                    writer.write(chunk);
+                    in_big_wild = false;
+                }
             }
-            writer.write(DOUBLE_WILD)
+        } else if chunk == DOUBLE_WILD {
+            in_big_wild = true;
+        } else {
+            writer.write_byte(DELIMITER);
+            writer.write(dollar_star_chunk(chunk, &mut report));
         }
-        *self = unsafe {
-            str::from_utf8_unchecked_mut(slice::from_raw_parts_mut(writer.ptr, writer.len))
+    }
+    if in_big_wild {
+        if writer.len != 0 {
+            writer.write_byte(DELIMITER);
         }
+        writer.write(DOUBLE_WILD)
+    }
+    *s = unsafe { str::from_utf8_unchecked_mut(slice::from_raw_parts_mut(writer.ptr, writer.len)) }
+}
+
+// Returns the chunk to write in place of `chunk`, i.e. `*` for a `$*` chunk.
+fn dollar_star_chunk<'a, F: FnMut(Normalization)>(chunk: &'a [u8], report: &mut F) -> &'a [u8] {
+    if chunk == b"$*" {
+        report(Normalization::DollarStarChunk);
+        b"*"
+    } else {
+        chunk
     }
 }
 
+// Reports the rewrite rules applied when the `*` or `$*` `chunk` is moved before a `**` chunk.
+fn report_wild<F: FnMut(Normalization)>(chunk: &[u8], report: &mut F) {
+    dollar_star_chunk(chunk, report);
+    report(Normalization::ReorderedDoubleWild);
+}
+
 impl Canonizable for String {
     fn canonize(&mut self) {
         let mut s = self.as_mut();
@@ -122,6 +147,35 @@ impl Canonizable for String {
     }
 }
 
+/// A rewrite rule applied by the canonization of a key expression,
+/// as reported by [`keyexpr::canonicalize_verbose`](super::keyexpr::canonicalize_verbose).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// A contiguous sequence of `$*` was replaced by a single `$*` (e.g. `a$*$*` => `a$*`).
+    CollapsedDollarStars,
+    /// A `$*` chunk was replaced by a `*` chunk (e.g. `a/$*` => `a/*`).
+    DollarStarChunk,
+    /// A contiguous sequence of `**` chunks was replaced by a single `**` chunk (e.g. `a/**/**` => `a/**`).
+    CollapsedDoubleWilds,
+    /// A `**/*` sequence was replaced by `*/**` (e.g. `a/**/*` => `a/*/**`).
+    ReorderedDoubleWild,
+}
+
+// Canonizes `s` in place, returning the rewrite rules that were applied, in the order they are listed in `Normalization`.
+pub(crate) fn canonize_verbose(s: &mut String) -> Vec<Normalization> {
+    let mut result = Vec::new();
+    let mut ke = s.as_mut();
+    canonize_reporting(&mut ke, |normalization| {
+        if !result.contains(&normalization) {
+            result.push(normalization)
+        }
+    });
+    let len = ke.len();
+    s.truncate(len);
+    result.sort_unstable_by_key(|normalization| *normalization as u8);
+    result
+}
+
 #[test]
 fn canonizer() {
     use super::OwnedKeyExpr;
@@ -171,3 +225,74 @@ fn canonizer() {
     s.canonize();
     assert_eq!(s, "hello/*/**");
 }
+
+#[test]
+fn canonizer_normalizations() {
+    use super::keyexpr;
+    let (ke, normalizations) = keyexpr::canonicalize_verbose("hello/bye").unwrap();
+    assert_eq!(ke.as_str(), "hello/bye");
+    assert!(normalizations.is_empty());
+
+    let (ke, normalizations) = keyexpr::canonicalize_verbose("hello/foo$*$*/bar").unwrap();
+    assert_eq!(ke.as_str(), "hello/foo$*/bar");
+    assert_eq!(normalizations, [Normalization::CollapsedDollarStars]);
+
+    let (ke, normalizations) = keyexpr::canonicalize_verbose("$*$*/hello").unwrap();
+    assert_eq!(ke.as_str(), "*/hello");
+    assert_eq!(
+        normalizations,
+        [
+            Normalization::CollapsedDollarStars,
+            Normalization::DollarStarChunk
+        ]
+    );
+
+    let (ke, normalizations) = keyexpr::canonicalize_verbose("hello/**/**/bye").unwrap();
+    assert_eq!(ke.as_str(), "hello/**/bye");
+    assert_eq!(normalizations, [Normalization::CollapsedDoubleWilds]);
+
+    let (ke, normalizations) = keyexpr::canonicalize_verbose("hello/**/**/$*").unwrap();
+    assert_eq!(ke.as_str(), "hello/*/**");
+    assert_eq!(
+        normalizations,
+        [
+            Normalization::DollarStarChunk,
+            Normalization::CollapsedDoubleWilds,
+            Normalization::ReorderedDoubleWild
+        ]
+    );
+
+    let (ke, normalizations) = keyexpr::canonicalize_verbose("a/**/*/**").unwrap();
+    assert_eq!(ke.as_str(), "a/*/**");
+    assert_eq!(
+        normalizations,
+        [
+            Normalization::CollapsedDoubleWilds,
+            Normalization::ReorderedDoubleWild
+        ]
+    );
+
+    // The normalizations are only reported when the canonization changes the key expression
+    for s in [
+        "a/b",
+        "a/*/**",
+        "**/a",
+        "a$*b/**",
+        "a/**/*/**",
+        "$*/**/$*$*/**",
+        "**/**/a/$*/**/*",
+        "a/**/b/**/$*$*",
+    ] {
+        let mut canon = String::from(s);
+        canon.canonize();
+        let (ke, normalizations) = keyexpr::canonicalize_verbose(s).unwrap();
+        assert_eq!(ke.as_str(), canon);
+        assert_eq!(
+            normalizations.is_empty(),
+            canon == s,
+            "{s}: {normalizations:?}"
+        );
+    }
+
+    assert!(keyexpr::canonicalize_verbose("hello//bye").is_err());
+}
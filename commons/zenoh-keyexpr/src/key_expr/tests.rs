@@ -146,6 +146,35 @@ fn inclusions() {
     assert!(!includes("x/c$*", "x/abc$*"));
     assert!(includes("x/$*c$*", "x/abc$*"));
     assert!(!includes("x/$*d", "x/$*e"));
+    assert!(!includes("x/*", "x/**"));
+    assert!(!includes("x/*/c", "x/**/c"));
+    assert!(!includes("a/*/c/*", "a/**/c/**"));
+}
+
+fn strictly_includes<'a>(l: &'a str, r: &'a str) -> bool {
+    let left: &keyexpr = l.try_into().unwrap();
+    let right: &keyexpr = r.try_into().unwrap();
+    left.strictly_includes(right)
+}
+
+#[test]
+fn strict_inclusions() {
+    assert!(strictly_includes("**", "a"));
+    assert!(strictly_includes("**", "a/b/c"));
+    assert!(strictly_includes("**", "a/**"));
+    assert!(!strictly_includes("**", "**"));
+    assert!(!strictly_includes("a", "a"));
+    assert!(!strictly_includes("a", "**"));
+    assert!(strictly_includes("a/**", "a/*/c"));
+    assert!(strictly_includes("a/**", "a"));
+    assert!(!strictly_includes("a/*/c", "a/**"));
+    assert!(strictly_includes("a/**/c/**", "a/*/c/*"));
+    assert!(strictly_includes("a/**/c/*", "a/b/**/c/d"));
+    assert!(!strictly_includes("a/**/c/*", "a/**/c/**"));
+    assert!(strictly_includes("a/$*b", "a/xb"));
+    assert!(!strictly_includes("a/$*b", "a/**"));
+    assert!(!strictly_includes("a/$*b/**", "a/**/$*b"));
+    assert!(!strictly_includes("a/*", "b/*"));
 }
 
 #[test]
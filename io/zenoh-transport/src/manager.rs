@@ -466,6 +466,11 @@ impl TransportManager {
         // @TODO: multicast
     }
 
+    pub fn get_transports_zid(&self) -> Vec<ZenohId> {
+        self.get_transports_unicast_zid()
+        // @TODO: multicast
+    }
+
     pub fn get_locators(&self) -> Vec<Locator> {
         self.get_locators_unicast()
         // @TODO: multicast
@@ -479,6 +484,14 @@ impl TransportManager {
         // @TODO: multicast
     }
 
+    pub fn get_transport_after(
+        &self,
+        peer: Option<&ZenohId>,
+    ) -> Option<(ZenohId, TransportUnicast)> {
+        self.get_transport_unicast_after(peer)
+        // @TODO: multicast
+    }
+
     pub fn get_transports(&self) -> Vec<TransportUnicast> {
         self.get_transports_unicast()
         // @TODO: multicast
@@ -88,8 +88,15 @@ impl<P: 'static + Primitives> TransportPeerEventHandler for DeMux<P> {
                 }
                 Some(rep) => match rep.replier {
                     Some(replier) => {
-                        self.primitives
-                            .send_reply_data(rep.qid, replier.id, key, data_info, payload);
+                        self.primitives.send_reply_data_with_qos(
+                            rep.qid,
+                            replier.id,
+                            key,
+                            data_info,
+                            payload,
+                            msg.channel,
+                            congestion_control,
+                        );
                     }
                     None => {
                         bail!("ReplyData with no replier_id")
@@ -79,6 +79,25 @@ pub trait Primitives: Send + Sync {
         payload: ZBuf,
     );
 
+    /// Same as [`send_reply_data`](Primitives::send_reply_data), with the given channel and
+    /// congestion control instead of the default ones of the replies.
+    ///
+    /// The default implementation ignores them and calls
+    /// [`send_reply_data`](Primitives::send_reply_data).
+    #[allow(clippy::too_many_arguments)]
+    fn send_reply_data_with_qos(
+        &self,
+        qid: ZInt,
+        replier_id: ZenohId,
+        key_expr: WireExpr,
+        info: Option<DataInfo>,
+        payload: ZBuf,
+        _channel: Channel,
+        _congestion_control: CongestionControl,
+    ) {
+        self.send_reply_data(qid, replier_id, key_expr, info, payload)
+    }
+
     fn send_reply_final(&self, qid: ZInt);
 
     fn send_pull(
@@ -184,11 +184,32 @@ impl Primitives for Mux {
         data_info: Option<DataInfo>,
         payload: ZBuf,
     ) {
-        let _ = self.handler.handle_message(ZenohMessage::make_data(
-            key_expr.to_owned(),
+        self.send_reply_data_with_qos(
+            qid,
+            replier_id,
+            key_expr,
+            data_info,
             payload,
             zmsg::default_channel::REPLY,
             zmsg::default_congestion_control::REPLY,
+        );
+    }
+
+    fn send_reply_data_with_qos(
+        &self,
+        qid: ZInt,
+        replier_id: ZenohId,
+        key_expr: WireExpr,
+        data_info: Option<DataInfo>,
+        payload: ZBuf,
+        channel: Channel,
+        congestion_control: CongestionControl,
+    ) {
+        let _ = self.handler.handle_message(ZenohMessage::make_data(
+            key_expr.to_owned(),
+            payload,
+            channel,
+            congestion_control,
             data_info,
             None,
             Some(ReplyContext::new(qid, Some(ReplierInfo { id: replier_id }))),
@@ -384,11 +405,32 @@ impl Primitives for McastMux {
         data_info: Option<DataInfo>,
         payload: ZBuf,
     ) {
-        let _ = self.handler.handle_message(ZenohMessage::make_data(
-            key_expr.to_owned(),
+        self.send_reply_data_with_qos(
+            qid,
+            replier_id,
+            key_expr,
+            data_info,
             payload,
             zmsg::default_channel::REPLY,
             zmsg::default_congestion_control::REPLY,
+        );
+    }
+
+    fn send_reply_data_with_qos(
+        &self,
+        qid: ZInt,
+        replier_id: ZenohId,
+        key_expr: WireExpr,
+        data_info: Option<DataInfo>,
+        payload: ZBuf,
+        channel: Channel,
+        congestion_control: CongestionControl,
+    ) {
+        let _ = self.handler.handle_message(ZenohMessage::make_data(
+            key_expr.to_owned(),
+            payload,
+            channel,
+            congestion_control,
             data_info,
             None,
             Some(ReplyContext::new(qid, Some(ReplierInfo { id: replier_id }))),
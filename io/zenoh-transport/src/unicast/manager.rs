@@ -519,6 +519,19 @@ impl TransportManager {
             .map(|t| t.into())
     }
 
+    // Returns the transport of the peer with the smallest ZenohId greater than the given one,
+    // or with the smallest ZenohId if none is given.
+    pub fn get_transport_unicast_after(
+        &self,
+        peer: Option<&ZenohId>,
+    ) -> Option<(ZenohId, TransportUnicast)> {
+        zlock!(self.state.unicast.transports)
+            .iter()
+            .filter(|(zid, _)| peer.map_or(true, |peer| *zid > peer))
+            .min_by_key(|(zid, _)| **zid)
+            .map(|(zid, t)| (*zid, t.into()))
+    }
+
     pub fn get_transports_unicast(&self) -> Vec<TransportUnicast> {
         zlock!(self.state.unicast.transports)
             .values()
@@ -526,6 +539,13 @@ impl TransportManager {
             .collect()
     }
 
+    pub fn get_transports_unicast_zid(&self) -> Vec<ZenohId> {
+        zlock!(self.state.unicast.transports)
+            .keys()
+            .copied()
+            .collect()
+    }
+
     pub(super) async fn del_transport_unicast(&self, peer: &ZenohId) -> ZResult<()> {
         let _ = zlock!(self.state.unicast.transports)
             .remove(peer)
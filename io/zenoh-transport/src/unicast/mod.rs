@@ -25,6 +25,7 @@ use super::{TransportPeer, TransportPeerEventHandler};
 pub use manager::*;
 use std::fmt;
 use std::sync::{Arc, Weak};
+use std::time::Instant;
 use transport::TransportUnicastInner;
 use zenoh_link::Link;
 use zenoh_protocol::{
@@ -137,6 +138,12 @@ impl TransportUnicast {
         Ok(transport.get_callback())
     }
 
+    #[inline(always)]
+    pub fn get_established(&self) -> ZResult<Instant> {
+        let transport = self.get_inner()?;
+        Ok(transport.get_established())
+    }
+
     pub fn get_peer(&self) -> ZResult<TransportPeer> {
         let transport = self.get_inner()?;
         let tp = TransportPeer {
@@ -18,7 +18,7 @@ use super::link::TransportLinkUnicast;
 use super::TransportUnicastStatsAtomic;
 use async_std::sync::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use zenoh_core::{zasynclock, zread, zwrite};
 use zenoh_link::{Link, LinkUnicast, LinkUnicastDirection};
 use zenoh_protocol::{
@@ -74,6 +74,8 @@ pub(crate) struct TransportUnicastInner {
     pub(super) callback: Arc<RwLock<Option<Arc<dyn TransportPeerEventHandler>>>>,
     // Mutex for notification
     pub(super) alive: Arc<AsyncMutex<bool>>,
+    // The time the transport has been established
+    pub(super) established: Instant,
     // Transport statistics
     #[cfg(feature = "stats")]
     pub(super) stats: Arc<TransportUnicastStatsAtomic>,
@@ -111,6 +113,7 @@ impl TransportUnicastInner {
             links: Arc::new(RwLock::new(vec![].into_boxed_slice())),
             callback: Arc::new(RwLock::new(None)),
             alive: Arc::new(AsyncMutex::new(false)),
+            established: Instant::now(),
             #[cfg(feature = "stats")]
             stats: Arc::new(TransportUnicastStatsAtomic::default()),
         };
@@ -386,6 +389,10 @@ impl TransportUnicastInner {
         zread!(self.callback).clone()
     }
 
+    pub(crate) fn get_established(&self) -> Instant {
+        self.established
+    }
+
     /*************************************/
     /*           TERMINATION             */
     /*************************************/
@@ -0,0 +1,243 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use async_std::prelude::FutureExt;
+use async_std::task;
+use std::any::Any;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zenoh_buffers::ZBuf;
+use zenoh_core::{zasync_executor_init, zlock};
+use zenoh_link::Link;
+use zenoh_protocol::{
+    core::{
+        Channel, CongestionControl, EndPoint, Priority, Reliability, WhatAmI, WireExpr, ZenohId,
+    },
+    zenoh::{zmsg, ZenohBody, ZenohMessage},
+};
+use zenoh_result::ZResult;
+use zenoh_transport::{
+    Mux, Primitives, TransportEventHandler, TransportManager, TransportMulticast,
+    TransportMulticastEventHandler, TransportPeer, TransportPeerEventHandler, TransportUnicast,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+const SLEEP: Duration = Duration::from_secs(1);
+const SLEEP_COUNT: Duration = Duration::from_millis(10);
+
+macro_rules! ztimeout {
+    ($f:expr) => {
+        $f.timeout(TIMEOUT).await.unwrap()
+    };
+}
+
+// Transport Handler for the router
+#[derive(Default)]
+struct SHRouter {
+    messages: Arc<Mutex<Vec<ZenohMessage>>>,
+}
+
+impl TransportEventHandler for SHRouter {
+    fn new_unicast(
+        &self,
+        _peer: TransportPeer,
+        _transport: TransportUnicast,
+    ) -> ZResult<Arc<dyn TransportPeerEventHandler>> {
+        Ok(Arc::new(SCRouter {
+            messages: self.messages.clone(),
+        }))
+    }
+
+    fn new_multicast(
+        &self,
+        _transport: TransportMulticast,
+    ) -> ZResult<Arc<dyn TransportMulticastEventHandler>> {
+        panic!();
+    }
+}
+
+// Transport Callback for the router
+struct SCRouter {
+    messages: Arc<Mutex<Vec<ZenohMessage>>>,
+}
+
+impl TransportPeerEventHandler for SCRouter {
+    fn handle_message(&self, message: ZenohMessage) -> ZResult<()> {
+        zlock!(self.messages).push(message);
+        Ok(())
+    }
+
+    fn new_link(&self, _link: Link) {}
+    fn del_link(&self, _link: Link) {}
+    fn closing(&self) {}
+    fn closed(&self) {}
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Transport Handler for the client
+struct SHClient;
+
+impl TransportEventHandler for SHClient {
+    fn new_unicast(
+        &self,
+        _peer: TransportPeer,
+        _transport: TransportUnicast,
+    ) -> ZResult<Arc<dyn TransportPeerEventHandler>> {
+        Ok(Arc::new(SCClient))
+    }
+
+    fn new_multicast(
+        &self,
+        _transport: TransportMulticast,
+    ) -> ZResult<Arc<dyn TransportMulticastEventHandler>> {
+        panic!();
+    }
+}
+
+// Transport Callback for the client
+struct SCClient;
+
+impl TransportPeerEventHandler for SCClient {
+    fn handle_message(&self, _message: ZenohMessage) -> ZResult<()> {
+        Ok(())
+    }
+
+    fn new_link(&self, _link: Link) {}
+    fn del_link(&self, _link: Link) {}
+    fn closing(&self) {}
+    fn closed(&self) {}
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+async fn run(endpoint: &EndPoint) {
+    // Define client and router IDs
+    let client_id = ZenohId::try_from([1]).unwrap();
+    let router_id = ZenohId::try_from([2]).unwrap();
+
+    // Create the router transport manager
+    let router_handler = Arc::new(SHRouter::default());
+    let router_manager = TransportManager::builder()
+        .whatami(WhatAmI::Router)
+        .zid(router_id)
+        .build(router_handler.clone())
+        .unwrap();
+
+    // Create the client transport manager
+    let client_manager = TransportManager::builder()
+        .whatami(WhatAmI::Client)
+        .zid(client_id)
+        .build(Arc::new(SHClient))
+        .unwrap();
+
+    println!("Add locator: {endpoint}");
+    let _ = ztimeout!(router_manager.add_listener(endpoint.clone())).unwrap();
+    println!("Opening transport with {endpoint}");
+    let _ = ztimeout!(client_manager.open_transport(endpoint.clone())).unwrap();
+    let client_transport = client_manager.get_transport(&router_id).unwrap();
+
+    // Send a reply with the given QoS, then one with the default QoS of the replies
+    let mux = Mux::new(client_transport.clone());
+    let channel = Channel {
+        priority: Priority::InteractiveHigh,
+        reliability: Reliability::Reliable,
+    };
+    mux.send_reply_data_with_qos(
+        1,
+        client_id,
+        WireExpr::from("test/reply/qos"),
+        None,
+        ZBuf::from(vec![0_u8; 8]),
+        channel,
+        CongestionControl::Drop,
+    );
+    mux.send_reply_data(
+        2,
+        client_id,
+        WireExpr::from("test/reply/default"),
+        None,
+        ZBuf::from(vec![0_u8; 8]),
+    );
+
+    // Wait for the replies to arrive to the other side
+    ztimeout!(async {
+        while zlock!(router_handler.messages).len() != 2 {
+            task::sleep(SLEEP_COUNT).await;
+        }
+    });
+
+    // The replies are sent on distinct priorities, hence may be received in any order
+    let mut replies = zlock!(router_handler.messages)
+        .drain(..)
+        .map(|message| match message.body {
+            ZenohBody::Data(data) => (
+                data.reply_context.unwrap().qid,
+                message.channel,
+                data.congestion_control,
+            ),
+            _ => panic!("Unexpected message: {message:?}"),
+        })
+        .collect::<Vec<_>>();
+    replies.sort_by_key(|(qid, _, _)| *qid);
+    assert_eq!(
+        replies,
+        vec![
+            (1, channel, CongestionControl::Drop),
+            (
+                2,
+                zmsg::default_channel::REPLY,
+                zmsg::default_congestion_control::REPLY
+            ),
+        ]
+    );
+
+    // Close the transport
+    println!("Closing transport with {endpoint}");
+    ztimeout!(client_transport.close()).unwrap();
+    ztimeout!(async {
+        while !router_manager.get_transports().is_empty() {
+            task::sleep(SLEEP).await;
+        }
+    });
+
+    println!("Del locator: {endpoint}");
+    ztimeout!(router_manager.del_listener(endpoint)).unwrap();
+
+    // Wait a little bit
+    task::sleep(SLEEP).await;
+
+    ztimeout!(router_manager.close());
+    ztimeout!(client_manager.close());
+
+    // Wait a little bit
+    task::sleep(SLEEP).await;
+}
+
+#[cfg(feature = "transport_tcp")]
+#[test]
+fn reply_qos_tcp_only() {
+    let _ = env_logger::try_init();
+    task::block_on(async {
+        zasync_executor_init!();
+    });
+    // Define the locator
+    let endpoint: EndPoint = format!("tcp/127.0.0.1:{}", 10020).parse().unwrap();
+    // Run
+    task::block_on(run(&endpoint));
+}
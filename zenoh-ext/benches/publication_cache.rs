@@ -0,0 +1,76 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+#[macro_use]
+extern crate criterion;
+
+use async_std::task;
+use criterion::Criterion;
+use std::time::Duration;
+use zenoh::config::ModeDependentValue;
+use zenoh::prelude::sync::*;
+use zenoh_ext::SessionExt;
+
+// 500 * 100 = 50k cached resources
+const GROUPS: usize = 500;
+const KEYS_PER_GROUP: usize = 100;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut config = config::peer();
+    config.scouting.multicast.set_enabled(Some(false)).unwrap();
+    config
+        .timestamping
+        .set_enabled(Some(ModeDependentValue::Unique(true)))
+        .unwrap();
+    let session = zenoh::open(config).res().unwrap();
+
+    let cache = session
+        .declare_publication_cache("bench/**")
+        .resources_limit(GROUPS * KEYS_PER_GROUP)
+        .res()
+        .unwrap();
+    for group in 0..GROUPS {
+        for key in 0..KEYS_PER_GROUP {
+            session
+                .put(format!("bench/{group}/{key}"), "value")
+                .res()
+                .unwrap();
+        }
+    }
+    // wait for the cache to ingest all the publications
+    while cache.snapshot().res().unwrap().samples_count < GROUPS * KEYS_PER_GROUP {
+        task::block_on(task::sleep(Duration::from_millis(100)));
+    }
+
+    let query = |selector: &str| {
+        let replies = session
+            .get(selector)
+            .consolidation(ConsolidationMode::None)
+            .res()
+            .unwrap();
+        while replies.recv().is_ok() {}
+    };
+
+    // a wildcard query matching 100 of the 50k cached resources
+    c.bench_function("PublicationCache wildcard query 100/50k", |b| {
+        b.iter(|| query("bench/42/*"))
+    });
+
+    // a wildcard query matching 500 of the 50k cached resources
+    c.bench_function("PublicationCache wildcard query 500/50k", |b| {
+        b.iter(|| query("bench/*/42"))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+#[macro_use]
+extern crate criterion;
+
+use criterion::Criterion;
+use zenoh::config::ModeDependentValue;
+use zenoh::prelude::sync::*;
+use zenoh_ext::SessionExt;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut config = config::peer();
+    config.scouting.multicast.set_enabled(Some(false)).unwrap();
+    config
+        .timestamping
+        .set_enabled(Some(ModeDependentValue::Unique(true)))
+        .unwrap();
+    let session = zenoh::open(config).res().unwrap();
+
+    // the latency between a local publication and its caching, as notified by write_through,
+    // with the publications delivered to the cache's task through a channel or by a callback
+    for (name, callback_subscriber) in [("channel", false), ("callback", true)] {
+        let key_expr = format!("bench/ingestion/{name}");
+        let (cached_tx, cached_rx) = flume::unbounded();
+        let _cache = session
+            .declare_publication_cache(&key_expr)
+            .callback_subscriber(callback_subscriber)
+            .write_through(move |_| {
+                let _ = cached_tx.send(());
+            })
+            .res()
+            .unwrap();
+
+        c.bench_function(&format!("PublicationCache {name} ingestion"), |b| {
+            b.iter(|| {
+                session.put(&key_expr, "value").res().unwrap();
+                cached_rx.recv().unwrap();
+            })
+        });
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use async_std::task;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use zenoh::config::ModeDependentValue;
+use zenoh::prelude::sync::*;
+use zenoh_ext::SessionExt;
+
+// The number of bytes currently allocated by the process.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const KEYS: usize = 100_000;
+
+// Returns the memory used by a PublicationCache on `prefix/**` with the given history,
+// caching `history` publications of each of 100k resources.
+fn measure(session: &Session, prefix: &str, history: usize) -> usize {
+    // publish once beforehand, so that the allocations of the session are not measured
+    for key in 0..KEYS {
+        session
+            .put(format!("{prefix}/{key}"), "value")
+            .res()
+            .unwrap();
+    }
+    task::block_on(task::sleep(Duration::from_millis(500)));
+
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let cache = session
+        .declare_publication_cache(format!("{prefix}/**"))
+        .history(history)
+        .resources_limit(KEYS)
+        .res()
+        .unwrap();
+    for _ in 0..history {
+        for key in 0..KEYS {
+            session
+                .put(format!("{prefix}/{key}"), "value")
+                .res()
+                .unwrap();
+        }
+    }
+    // wait for the cache to ingest all the publications
+    while cache.stats().samples_cached < (history * KEYS) as u64 {
+        task::block_on(task::sleep(Duration::from_millis(100)));
+    }
+    let used = ALLOCATED.load(Ordering::Relaxed) - before;
+    println!(
+        "PublicationCache history({}) {}k resources: {} bytes ({} bytes per resource, {} per publication)",
+        history,
+        KEYS / 1000,
+        used,
+        used / KEYS,
+        used / (history * KEYS)
+    );
+    used
+}
+
+// Measures the memory used by a PublicationCache caching the latest publication of 100k resources,
+// with as reference a PublicationCache caching the 2 latest ones, whose samples are kept in a VecDeque.
+fn main() {
+    let mut config = config::peer();
+    config.scouting.multicast.set_enabled(Some(false)).unwrap();
+    config
+        .timestamping
+        .set_enabled(Some(ModeDependentValue::Unique(true)))
+        .unwrap();
+    let session = zenoh::open(config).res().unwrap();
+    measure(&session, "bench/latest", 1);
+    measure(&session, "bench/history", 2);
+}
@@ -0,0 +1,226 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::{PublicationCache, PublicationCacheBuilder};
+use futures::future::BoxFuture;
+use std::time::Duration;
+use zenoh::prelude::r#async::*;
+use zenoh::publication::{Publication, Publisher};
+use zenoh::Session;
+use zenoh_core::{AsyncResolve, Resolvable, SyncResolve};
+use zenoh_result::{zerror, ZResult};
+use zenoh_util::core::ResolveFuture;
+
+/// The builder of [`AdvancedPublisher`], allowing to configure both its publisher
+/// and its [`PublicationCache`].
+pub struct AdvancedPublisherBuilder<'a, 'b, 'c> {
+    session: &'a Session,
+    key_expr: ZResult<KeyExpr<'b>>,
+    congestion_control: CongestionControl,
+    priority: Priority,
+    cache: PublicationCacheBuilder<'a, 'b, 'c>,
+}
+
+impl<'a, 'b, 'c> AdvancedPublisherBuilder<'a, 'b, 'c> {
+    pub(crate) fn new(
+        session: &'a Session,
+        key_expr: ZResult<KeyExpr<'b>>,
+    ) -> AdvancedPublisherBuilder<'a, 'b, 'c> {
+        // the error is copied for the cache, as it's reported by the publisher first anyway
+        let cache_key_expr = match &key_expr {
+            Ok(key_expr) => Ok(key_expr.clone()),
+            Err(e) => Err(zerror!("{}", e).into()),
+        };
+        AdvancedPublisherBuilder {
+            session,
+            key_expr,
+            congestion_control: CongestionControl::default(),
+            priority: Priority::default(),
+            cache: PublicationCacheBuilder::new(session, cache_key_expr),
+        }
+    }
+
+    /// Change the congestion control to apply when routing the publications.
+    #[inline]
+    pub fn congestion_control(mut self, congestion_control: CongestionControl) -> Self {
+        self.congestion_control = congestion_control;
+        self
+    }
+
+    /// Change the priority of the publications.
+    #[inline]
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Change the number of publications kept by the cache for each resource
+    /// (see [`PublicationCacheBuilder::history`]).
+    #[inline]
+    pub fn cache_history(mut self, history: usize) -> Self {
+        self.cache = self.cache.history(history);
+        self
+    }
+
+    /// Change the time after which the cached publications expire
+    /// (see [`PublicationCacheBuilder::time_expiration`]).
+    #[inline]
+    pub fn cache_time_expiration(mut self, time_expiration: Duration) -> Self {
+        self.cache = self.cache.time_expiration(time_expiration);
+        self
+    }
+
+    /// Change the prefix prepended to the key expression of the cache's queryable
+    /// (see [`PublicationCacheBuilder::queryable_prefix`]).
+    #[inline]
+    pub fn cache_queryable_prefix<TryIntoKeyExpr>(
+        mut self,
+        queryable_prefix: TryIntoKeyExpr,
+    ) -> Self
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'c>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'c>>>::Error: Into<zenoh_result::Error>,
+    {
+        self.cache = self.cache.queryable_prefix(queryable_prefix);
+        self
+    }
+
+    /// Configure the cache with any other option of the [`PublicationCacheBuilder`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use zenoh::prelude::r#async::*;
+    /// use zenoh_ext::*;
+    ///
+    /// let session = zenoh_ext::open_with_cache_support(&config::peer()).res().await.unwrap();
+    /// let publisher = session
+    ///     .declare_advanced_publisher("key/expression")
+    ///     .cache_history(10)
+    ///     .cache(|cache| cache.close_timeout(Duration::from_secs(1)).delete_evicts(true))
+    ///     .res()
+    ///     .await
+    ///     .unwrap();
+    /// publisher.put("value").res().await.unwrap();
+    /// # })
+    /// ```
+    #[inline]
+    pub fn cache<Configure>(mut self, configure: Configure) -> Self
+    where
+        Configure:
+            FnOnce(PublicationCacheBuilder<'a, 'b, 'c>) -> PublicationCacheBuilder<'a, 'b, 'c>,
+    {
+        self.cache = configure(self.cache);
+        self
+    }
+}
+
+impl<'a> Resolvable for AdvancedPublisherBuilder<'a, '_, '_> {
+    type To = ZResult<AdvancedPublisher<'a>>;
+}
+
+impl SyncResolve for AdvancedPublisherBuilder<'_, '_, '_> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        // like the PublicationCache, without starting the async-std executor
+        futures::executor::block_on(AdvancedPublisher::new(self))
+    }
+}
+
+impl<'a, 'b: 'a, 'c: 'a> AsyncResolve for AdvancedPublisherBuilder<'a, 'b, 'c> {
+    type Future = BoxFuture<'a, Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        Box::pin(AdvancedPublisher::new(self))
+    }
+}
+
+/// A [`Publisher`] bundled with a [`PublicationCache`] on the same key expression,
+/// declared via [`declare_advanced_publisher`](crate::SessionExt::declare_advanced_publisher).
+///
+/// The publications are routed to the session's local subscribers as to the remote ones,
+/// hence are cached like any local publication on the key expression, before being replied
+/// to the queries of [`QueryingSubscriber`](crate::QueryingSubscriberBuilder)s or of
+/// [`get_history`](crate::SessionExt::get_history).
+///
+/// Dropping the AdvancedPublisher undeclares its publisher, then drops its cache
+/// (see [`PublicationCache::close`]); [`close`](AdvancedPublisher::close) also waits for the
+/// cache's task to terminate.
+pub struct AdvancedPublisher<'a> {
+    // declared first to be dropped first, so that no publication is made once the cache is closed
+    publisher: Publisher<'a>,
+    cache: PublicationCache<'a>,
+}
+
+impl<'a> AdvancedPublisher<'a> {
+    async fn new(conf: AdvancedPublisherBuilder<'a, '_, '_>) -> ZResult<AdvancedPublisher<'a>> {
+        let key_expr = conf.key_expr?.into_owned();
+        // the cache is declared first so that it caches the very first publication
+        let cache = PublicationCache::new(conf.cache).await?;
+        let publisher = conf
+            .session
+            .declare_publisher(key_expr)
+            .congestion_control(conf.congestion_control)
+            .priority(conf.priority)
+            .res_async()
+            .await?;
+        Ok(AdvancedPublisher { publisher, cache })
+    }
+
+    /// Return the key expression of this AdvancedPublisher.
+    #[inline]
+    pub fn key_expr(&self) -> &KeyExpr<'a> {
+        self.publisher.key_expr()
+    }
+
+    /// Return the cache of this AdvancedPublisher, e.g. to inspect its content.
+    #[inline]
+    pub fn cache(&self) -> &PublicationCache<'a> {
+        &self.cache
+    }
+
+    /// Publish a value or a deletion, that is cached as well.
+    #[inline]
+    pub fn write<IntoValue>(&self, kind: SampleKind, value: IntoValue) -> Publication
+    where
+        IntoValue: Into<Value>,
+    {
+        self.publisher.write(kind, value)
+    }
+
+    /// Put a value, that is cached as well.
+    #[inline]
+    pub fn put<IntoValue>(&self, value: IntoValue) -> Publication
+    where
+        IntoValue: Into<Value>,
+    {
+        self.publisher.put(value)
+    }
+
+    /// Delete the value, the deletion being cached as well.
+    #[inline]
+    pub fn delete(&self) -> Publication {
+        self.publisher.delete()
+    }
+
+    /// Close this AdvancedPublisher, undeclaring its publisher then closing its cache
+    /// (see [`PublicationCache::close`]).
+    #[inline]
+    pub fn close(self) -> impl Resolve<ZResult<()>> + 'a {
+        ResolveFuture::new(async move {
+            let AdvancedPublisher { publisher, cache } = self;
+            publisher.undeclare().res_async().await?;
+            cache.close().res_async().await
+        })
+    }
+}
@@ -0,0 +1,252 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::record::{decode_record, encode_record};
+use miniz_oxide::inflate::TINFLStatus;
+use std::convert::TryInto;
+use zenoh::buffers::{SplitBuffer, ZBuf};
+use zenoh::prelude::{Encoding, KeyExpr, Sample, Value};
+use zenoh::query::Reply;
+use zenoh_result::{bail, zerror, ZResult};
+
+/// The selector parameter with which a query asks a
+/// [`PublicationCache`](crate::PublicationCache) to reply with a bundle,
+/// e.g. `key/expr?_encoding=bundle` (see [`BUNDLE_ENCODING`]), with the default
+/// [`control_param_prefix`](crate::PublicationCacheBuilder::control_param_prefix).
+pub const ENCODING_KEY: &str = "_encoding";
+
+/// The value of the [`ENCODING_KEY`] selector parameter asking for a bundle.
+pub const BUNDLE: &str = "bundle";
+
+/// The encoding of a bundle: a single payload aggregating several publications,
+/// replied by a [`PublicationCache`](crate::PublicationCache) to the queries asking for it
+/// with the [`ENCODING_KEY`] selector parameter, and unpacked with [`unbundle`].
+///
+/// The payload of a bundle is a gzip stream (RFC 1952) made of a single member,
+/// which decompresses to the sequence of the bundled publications. Each publication
+/// is made of the length of its content as a u32, followed by its content:
+///  - the key expression, the encoding (as a string) and the payload, each as a u32 length
+///    followed by the bytes
+///  - the kind as a u8 (`0` for a put, `1` for a delete)
+///  - `0u8` if there is no timestamp, or `1u8` followed by the timestamp's NTP64 time as a u64
+///    and the timestamp's id as a u32 length followed by the bytes
+///  - only if the publication has a source info: `0u8` if there is no source id, or `1u8`
+///    followed by the source id as a u32 length followed by the bytes, and then `0u8` if there
+///    is no source sequence number, or `1u8` followed by the source sequence number as a u64
+///
+/// All integers are little-endian. The decompressed sequence is at most [`MAX_BUNDLE_SIZE`] bytes.
+///
+/// The bundle itself is replied on the key expression of the query, without timestamp: the
+/// bundles replied by different caches are only all received by a query with no consolidation.
+pub const BUNDLE_ENCODING: &str = "application/x-zenoh-bundle+gzip";
+
+/// The maximum size of the decompressed content of a bundle (64 MiB).
+///
+/// [`unbundle`] rejects the larger bundles without decompressing them further, and a
+/// [`PublicationCache`](crate::PublicationCache) replies with the publications themselves
+/// instead of a bundle when they don't fit in it.
+pub const MAX_BUNDLE_SIZE: usize = 64 * 1024 * 1024;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const GZIP_DEFLATE: u8 = 8;
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+// the OS field of the gzip header for an unknown OS
+const GZIP_OS_UNKNOWN: u8 = 0xff;
+const GZIP_COMPRESSION_LEVEL: u8 = 6;
+const CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+/// Returns the publications bundled in the given sample, that must have the [`BUNDLE_ENCODING`].
+pub fn unbundle(sample: &Sample) -> ZResult<Vec<Sample>> {
+    if sample.value.encoding.to_string() != BUNDLE_ENCODING {
+        bail!(
+            "{} is not a bundle: its encoding is {} instead of {}",
+            sample.key_expr,
+            sample.value.encoding,
+            BUNDLE_ENCODING
+        );
+    }
+    let records = gunzip(&sample.value.payload.contiguous(), MAX_BUNDLE_SIZE)
+        .map_err(|e| zerror!("Invalid bundle on {}: {}", sample.key_expr, e))?;
+    let mut samples = Vec::new();
+    let mut offset = 0;
+    while offset < records.len() {
+        let (sample, len) = decode_record(&records[offset..]).ok_or_else(|| {
+            zerror!(
+                "Invalid bundle on {}: invalid publication at offset {}",
+                sample.key_expr,
+                offset
+            )
+        })?;
+        samples.push(sample);
+        offset += len;
+    }
+    Ok(samples)
+}
+
+// Returns the bundle of the given publications, on the given key expression,
+// or None if they exceed MAX_BUNDLE_SIZE.
+pub(crate) fn bundle(key_expr: &KeyExpr, samples: &[Sample]) -> Option<Sample> {
+    let records: Vec<u8> = samples.iter().flat_map(encode_record).collect();
+    if records.len() > MAX_BUNDLE_SIZE {
+        return None;
+    }
+    Some(Sample::new(
+        key_expr.clone().into_owned(),
+        Value::new(ZBuf::from(gzip(&records))).encoding(Encoding::from(BUNDLE_ENCODING)),
+    ))
+}
+
+// Wraps the callback notified of the replies to a query, so that it's notified of each
+// publication of the bundles, in place of the bundles.
+pub(crate) fn unbundling(
+    callback: Box<dyn Fn(Reply) + Send + Sync>,
+) -> Box<dyn Fn(Reply) + Send + Sync> {
+    Box::new(move |reply| match &reply.sample {
+        Ok(sample) if sample.value.encoding.to_string() == BUNDLE_ENCODING => {
+            match unbundle(sample) {
+                Ok(samples) => {
+                    for sample in samples {
+                        // Reply is non-exhaustive: update a copy of the bundle reply
+                        let mut reply = reply.clone();
+                        reply.sample = Ok(sample);
+                        callback(reply);
+                    }
+                }
+                Err(e) => log::warn!("Ignoring reply from {}: {}", reply.replier_id, e),
+            }
+        }
+        _ => callback(reply),
+    })
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let deflated = miniz_oxide::deflate::compress_to_vec(bytes, GZIP_COMPRESSION_LEVEL);
+    let mut gzip = Vec::with_capacity(18 + deflated.len());
+    gzip.extend_from_slice(&GZIP_MAGIC);
+    // no flags, no modification time, no extra flags
+    gzip.extend_from_slice(&[GZIP_DEFLATE, 0, 0, 0, 0, 0, 0, GZIP_OS_UNKNOWN]);
+    gzip.extend_from_slice(&deflated);
+    gzip.extend_from_slice(&CRC32.checksum(bytes).to_le_bytes());
+    gzip.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    gzip
+}
+
+// Decompresses the given gzip stream, failing if it decompresses to more than max_size bytes.
+fn gunzip(gzip: &[u8], max_size: usize) -> ZResult<Vec<u8>> {
+    if gzip.len() < 18 || gzip[..2] != GZIP_MAGIC || gzip[2] != GZIP_DEFLATE {
+        bail!("not a gzip stream");
+    }
+    let flags = gzip[3];
+    // the stream after the fixed part of the header
+    let (mut rest, trailer) = (&gzip[10..], &gzip[gzip.len() - 8..]);
+    if flags & GZIP_FEXTRA != 0 {
+        let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        rest = rest
+            .get(2 + len..)
+            .ok_or_else(|| zerror!("truncated gzip header"))?;
+    }
+    for flag in [GZIP_FNAME, GZIP_FCOMMENT] {
+        if flags & flag != 0 {
+            let len = rest
+                .iter()
+                .position(|b| *b == 0)
+                .ok_or_else(|| zerror!("truncated gzip header"))?;
+            rest = &rest[len + 1..];
+        }
+    }
+    if flags & GZIP_FHCRC != 0 {
+        rest = rest
+            .get(2..)
+            .ok_or_else(|| zerror!("truncated gzip header"))?;
+    }
+    if rest.len() < 8 {
+        bail!("truncated gzip stream");
+    }
+    let deflated = &rest[..rest.len() - 8];
+    let bytes = miniz_oxide::inflate::decompress_to_vec_with_limit(deflated, max_size).map_err(
+        |e| match e.status {
+            TINFLStatus::HasMoreOutput => zerror!("gzip stream exceeds {} bytes", max_size),
+            _ => zerror!("invalid gzip stream: {}", e),
+        },
+    )?;
+    let crc = u32::from_le_bytes(trailer[..4].try_into()?);
+    let len = u32::from_le_bytes(trailer[4..].try_into()?);
+    if CRC32.checksum(&bytes) != crc || bytes.len() as u32 != len {
+        bail!("gzip checksum mismatch");
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<u8> {
+        (0..1000u32).flat_map(u32::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn gunzip_round_trip() {
+        let records = records();
+        assert_eq!(gunzip(&gzip(&records), MAX_BUNDLE_SIZE).unwrap(), records);
+        assert_eq!(
+            gunzip(&gzip(&[]), MAX_BUNDLE_SIZE).unwrap(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn gunzip_truncated_stream() {
+        let gzip = gzip(&records());
+        for len in 0..gzip.len() {
+            assert!(
+                gunzip(&gzip[..len], MAX_BUNDLE_SIZE).is_err(),
+                "{len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn gunzip_corrupted_stream() {
+        let records = records();
+        let gzip = gzip(&records);
+        // a corrupted byte after the fixed part of the header (which has no check) is detected,
+        // unless it's a part of the deflate stream that doesn't change its content
+        // (e.g. the code length of an unused symbol)
+        for i in 10..gzip.len() {
+            let mut corrupted = gzip.clone();
+            corrupted[i] ^= 0xff;
+            if let Ok(bytes) = gunzip(&corrupted, MAX_BUNDLE_SIZE) {
+                assert_eq!(bytes, records, "byte {i}");
+            }
+        }
+        // while a corrupted checksum, length or magic number always is
+        for i in [gzip.len() - 8, gzip.len() - 1, 0] {
+            let mut corrupted = gzip.clone();
+            corrupted[i] ^= 0xff;
+            assert!(gunzip(&corrupted, MAX_BUNDLE_SIZE).is_err(), "byte {i}");
+        }
+    }
+
+    #[test]
+    fn gunzip_size_limit() {
+        let records = records();
+        let gzip = gzip(&records);
+        assert_eq!(gunzip(&gzip, records.len()).unwrap(), records);
+        let e = gunzip(&gzip, records.len() - 1).unwrap_err();
+        assert!(e.to_string().contains("exceeds"), "{e}");
+    }
+}
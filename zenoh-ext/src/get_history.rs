@@ -0,0 +1,156 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use zenoh::handlers::DefaultHandler;
+use zenoh::prelude::r#async::*;
+use zenoh::query::{GetBuilder, QueryTarget, Reply};
+use zenoh::Session;
+use zenoh_core::{AsyncResolve, Resolvable, SyncResolve};
+use zenoh_result::{zerror, ZResult};
+
+/// The builder of a query for the history of publications on a key expression,
+/// as returned by [`SessionExt::get_history()`](crate::SessionExt::get_history).
+///
+/// The query targets all the matching queryables (typically [`PublicationCache`](crate::PublicationCache)s)
+/// without consolidation, and the replies are collected and sorted by timestamp.
+///
+/// # Examples
+/// ```no_run
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+/// use zenoh_ext::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let samples = session
+///     .get_history("key/expression/**")
+///     .history(10)
+///     .consolidate()
+///     .res()
+///     .await
+///     .unwrap();
+/// for sample in samples {
+///     println!("{}: {}", sample.key_expr, sample.value);
+/// }
+/// # })
+/// ```
+pub struct GetHistoryBuilder<'a, 'b> {
+    pub(crate) session: &'a Session,
+    pub(crate) selector: ZResult<Selector<'b>>,
+    pub(crate) history: Option<usize>,
+    pub(crate) consolidate: bool,
+    pub(crate) timeout: Option<Duration>,
+}
+
+impl<'a, 'b> GetHistoryBuilder<'a, 'b> {
+    /// Keep only the `history` newest samples of each key expression (default: all the replied samples).
+    pub fn history(mut self, history: usize) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Deduplicate the replied samples by key expression and timestamp.
+    ///
+    /// This removes the copies of a same publication replied by several
+    /// [`PublicationCache`](crate::PublicationCache)s. The samples without timestamp are kept.
+    pub fn consolidate(mut self) -> Self {
+        self.consolidate = true;
+        self
+    }
+
+    /// Change the timeout of the query (default: the session's queries timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+// Declares the query for the history.
+fn get<'a, 'b: 'a>(
+    session: &'a Session,
+    selector: ZResult<Selector<'b>>,
+    timeout: Option<Duration>,
+) -> ZResult<GetBuilder<'a, 'b, DefaultHandler>> {
+    let selector = selector.map_err(|e| zerror!("Invalid selector for get_history: {}", e))?;
+    let mut get = session
+        .get(selector)
+        .target(QueryTarget::All)
+        .consolidation(ConsolidationMode::None);
+    if let Some(timeout) = timeout {
+        get = get.timeout(timeout);
+    }
+    Ok(get)
+}
+
+// Deduplicates, sorts and truncates the replied samples.
+fn collect(replies: Vec<Reply>, history: Option<usize>, consolidate: bool) -> Vec<Sample> {
+    let mut seen = HashSet::new();
+    let mut samples: Vec<Sample> = replies
+        .into_iter()
+        .filter_map(|reply| match reply.sample {
+            Ok(sample) => Some(sample),
+            Err(e) => {
+                log::warn!("Received error reply to get_history: {}", e);
+                None
+            }
+        })
+        .filter(|sample| match (consolidate, sample.timestamp) {
+            (true, Some(timestamp)) => seen.insert((sample.key_expr.clone(), timestamp)),
+            _ => true,
+        })
+        .collect();
+    // the samples without timestamp come first, in reception order
+    samples.sort_by_key(|sample| sample.timestamp);
+    if let Some(history) = history {
+        let mut counts: HashMap<KeyExpr<'static>, usize> = HashMap::new();
+        for sample in &samples {
+            *counts.entry(sample.key_expr.clone()).or_default() += 1;
+        }
+        samples.retain(|sample| {
+            let count = counts.get_mut(&sample.key_expr).unwrap();
+            *count -= 1;
+            *count < history
+        });
+    }
+    samples
+}
+
+impl Resolvable for GetHistoryBuilder<'_, '_> {
+    type To = ZResult<Vec<Sample>>;
+}
+
+impl<'a, 'b: 'a> SyncResolve for GetHistoryBuilder<'a, 'b> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        let receiver = get(self.session, self.selector, self.timeout)?.res_sync()?;
+        let replies = receiver.iter().collect();
+        Ok(collect(replies, self.history, self.consolidate))
+    }
+}
+
+impl<'a, 'b: 'a> AsyncResolve for GetHistoryBuilder<'a, 'b> {
+    type Future = Pin<Box<dyn Future<Output = Self::To> + Send + 'a>>;
+
+    fn res_async(self) -> Self::Future {
+        Box::pin(async move {
+            let receiver = get(self.session, self.selector, self.timeout)?
+                .res_async()
+                .await?;
+            let replies = receiver.into_stream().collect::<Vec<Reply>>().await;
+            Ok(collect(replies, self.history, self.consolidate))
+        })
+    }
+}
@@ -11,20 +11,41 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
+mod advanced_publisher;
+#[cfg(feature = "bundle")]
+mod bundle;
+mod get_history;
 pub mod group;
+#[cfg(feature = "persistence")]
+mod persistence;
 mod publication_cache;
 mod querying_subscriber;
+mod record;
+#[cfg(feature = "serde")]
+mod sample_ext;
 mod session_ext;
 mod subscriber_ext;
-pub use publication_cache::{PublicationCache, PublicationCacheBuilder};
+pub use advanced_publisher::{AdvancedPublisher, AdvancedPublisherBuilder};
+#[cfg(feature = "bundle")]
+pub use bundle::{unbundle, BUNDLE, BUNDLE_ENCODING, ENCODING_KEY, MAX_BUNDLE_SIZE};
+pub use get_history::GetHistoryBuilder;
+pub use publication_cache::{
+    list_caches, AnomalyKind, CacheDescriptor, CacheSnapshot, CacheState, CacheStats,
+    EvictedSample, EvictionReason, History, PublicationCache, PublicationCacheBuilder,
+    PublicationCacheError, QueryReplyStats, ReplyOrdering, ResourceEviction, SourceFilter,
+    TapEvent, TimestampAnomaly, KEYS_ONLY_KEY, KEY_ENCODING, MAX_KEY,
+};
 pub use querying_subscriber::{
-    FetchingSubscriber, FetchingSubscriberBuilder, QueryingSubscriberBuilder,
+    FetchingSubscriber, FetchingSubscriberBuilder, Gap, QueryingSubscriberBuilder,
 };
-pub use session_ext::SessionExt;
+#[cfg(feature = "serde")]
+pub use sample_ext::{SampleExt, APP_CBOR};
+pub use session_ext::{open_with_cache_support, SessionExt};
 pub use subscriber_ext::SubscriberBuilderExt;
 pub use subscriber_ext::SubscriberForward;
 
 /// The space of keys to use in a [`FetchingSubscriber`].
+#[derive(Debug, Clone, Copy)]
 pub enum KeySpace {
     User,
     Liveliness,
@@ -0,0 +1,101 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::record::{decode_record, encode_record};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use zenoh::prelude::Sample;
+use zenoh_result::ZResult;
+
+// The file is compacted once it holds more than COMPACTION_RATIO times as many records
+// as there are cached publications, and at least COMPACTION_MIN_RECORDS records.
+const COMPACTION_RATIO: usize = 2;
+const COMPACTION_MIN_RECORDS: usize = 1024;
+
+// The append-only file in which a PublicationCache persists the publications it caches,
+// as a sequence of records (see the record module).
+pub(crate) struct Persistence {
+    path: PathBuf,
+    file: File,
+    // the number of records in the file
+    records: usize,
+}
+
+impl Persistence {
+    // Opens (or creates) the file, and returns the publications it contains.
+    // The file is truncated at the last valid record, discarding a corrupted
+    // trailing record (typically partially written before a crash).
+    pub(crate) fn open(path: &Path) -> ZResult<(Persistence, Vec<Sample>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut samples = Vec::new();
+        let mut valid = 0;
+        while let Some((sample, len)) = decode_record(&bytes[valid..]) {
+            samples.push(sample);
+            valid += len;
+        }
+        if valid < bytes.len() {
+            log::warn!(
+                "Truncate {} corrupted bytes at the end of {}",
+                bytes.len() - valid,
+                path.display()
+            );
+            file.set_len(valid as u64)?;
+        }
+
+        Ok((
+            Persistence {
+                path: path.to_path_buf(),
+                file,
+                records: samples.len(),
+            },
+            samples,
+        ))
+    }
+
+    pub(crate) fn append(&mut self, sample: &Sample) -> io::Result<()> {
+        self.file.write_all(&encode_record(sample))?;
+        self.records += 1;
+        Ok(())
+    }
+
+    // Returns true if the file holds so many records compared to the given number of
+    // cached publications that it should be compacted.
+    pub(crate) fn needs_compaction(&self, cached: usize) -> bool {
+        self.records > COMPACTION_RATIO * cached.max(COMPACTION_MIN_RECORDS)
+    }
+
+    // Replaces the content of the file with the given publications.
+    pub(crate) fn compact<'a>(&mut self, samples: impl Iterator<Item = &'a Sample>) -> ZResult<()> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        let mut records = 0;
+        for sample in samples {
+            tmp.write_all(&encode_record(sample))?;
+            records += 1;
+        }
+        tmp.sync_all()?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.records = records;
+        Ok(())
+    }
+}
@@ -11,20 +11,24 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
-use std::collections::{btree_map, BTreeMap, VecDeque};
+use futures::select;
+use futures::FutureExt;
+use std::collections::{btree_map, BTreeMap, HashMap, VecDeque};
 use std::convert::TryInto;
 use std::future::Ready;
 use std::mem::swap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use zenoh::handlers::{locked, DefaultHandler};
+use zenoh::info::PeerEvent;
 use zenoh::prelude::r#async::*;
-use zenoh::query::{QueryConsolidation, QueryTarget, ReplyKeyExpr};
+use zenoh::query::{QueryConsolidation, QueryTarget, Reply, ReplyKeyExpr};
 use zenoh::subscriber::{Reliability, Subscriber};
 use zenoh::time::Timestamp;
 use zenoh::Result as ZResult;
-use zenoh::SessionRef;
+use zenoh::{Session, SessionRef};
 use zenoh_core::{zlock, AsyncResolve, Resolvable, SyncResolve};
+use zenoh_result::bail;
 
 /// The builder of [`FetchingSubscriber`], allowing to configure it.
 pub struct QueryingSubscriberBuilder<'a, 'b, KeySpace, Handler> {
@@ -38,6 +42,11 @@ pub struct QueryingSubscriberBuilder<'a, 'b, KeySpace, Handler> {
     pub(crate) query_consolidation: QueryConsolidation,
     pub(crate) query_accept_replies: ReplyKeyExpr,
     pub(crate) query_timeout: Duration,
+    pub(crate) history: Option<usize>,
+    pub(crate) fetch_on_reconnect: bool,
+    #[cfg(feature = "bundle")]
+    pub(crate) query_bundle: bool,
+    pub(crate) on_gap: Option<GapCallback>,
     pub(crate) handler: Handler,
 }
 
@@ -62,6 +71,11 @@ impl<'a, 'b, KeySpace> QueryingSubscriberBuilder<'a, 'b, KeySpace, DefaultHandle
             query_consolidation,
             query_accept_replies,
             query_timeout,
+            history,
+            fetch_on_reconnect,
+            #[cfg(feature = "bundle")]
+            query_bundle,
+            on_gap,
             handler: _,
         } = self;
         QueryingSubscriberBuilder {
@@ -75,6 +89,11 @@ impl<'a, 'b, KeySpace> QueryingSubscriberBuilder<'a, 'b, KeySpace, DefaultHandle
             query_consolidation,
             query_accept_replies,
             query_timeout,
+            history,
+            fetch_on_reconnect,
+            #[cfg(feature = "bundle")]
+            query_bundle,
+            on_gap,
             handler: callback,
         }
     }
@@ -115,6 +134,11 @@ impl<'a, 'b, KeySpace> QueryingSubscriberBuilder<'a, 'b, KeySpace, DefaultHandle
             query_consolidation,
             query_accept_replies,
             query_timeout,
+            history,
+            fetch_on_reconnect,
+            #[cfg(feature = "bundle")]
+            query_bundle,
+            on_gap,
             handler: _,
         } = self;
         QueryingSubscriberBuilder {
@@ -128,6 +152,11 @@ impl<'a, 'b, KeySpace> QueryingSubscriberBuilder<'a, 'b, KeySpace, DefaultHandle
             query_consolidation,
             query_accept_replies,
             query_timeout,
+            history,
+            fetch_on_reconnect,
+            #[cfg(feature = "bundle")]
+            query_bundle,
+            on_gap,
             handler,
         }
     }
@@ -182,6 +211,39 @@ impl<'a, 'b, Handler> QueryingSubscriberBuilder<'a, 'b, crate::UserSpace, Handle
         self
     }
 
+    /// Ask the queried [`PublicationCache`](crate::PublicationCache)s to reply with a single
+    /// bundle aggregating their compressed publications, instead of one reply per publication
+    /// (see [`BUNDLE_ENCODING`](crate::BUNDLE_ENCODING)).
+    ///
+    /// The bundles are unpacked, and their publications are merged with the live samples
+    /// like any other reply. This requires the queries not to be consolidated (the default),
+    /// and the caches to use the default
+    /// [`control_param_prefix`](crate::PublicationCacheBuilder::control_param_prefix).
+    #[cfg(feature = "bundle")]
+    #[inline]
+    pub fn query_bundle(mut self, query_bundle: bool) -> Self {
+        self.query_bundle = query_bundle;
+        self
+    }
+
+    /// Notify the given callback of the possible gaps between the replies to the queries and the
+    /// live publications, before the replies are merged with the live publications.
+    ///
+    /// For each key expression with both replies and live publications received during a fetch,
+    /// a [`Gap`] is notified if the newest reply is older than the oldest live publication: the
+    /// publications between them may have been missed (e.g. if they were evicted from the queried
+    /// caches). Only the timestamps of the publications are compared: a gap doesn't imply that
+    /// publications were actually missed, but the absence of gap implies that the replies and
+    /// the live publications overlap.
+    #[inline]
+    pub fn on_gap<Callback>(mut self, callback: Callback) -> Self
+    where
+        Callback: Fn(Gap) + Send + Sync + 'static,
+    {
+        self.on_gap = Some(Arc::new(callback));
+        self
+    }
+
     /// Change the consolidation mode to be used for queries.
     #[inline]
     pub fn query_consolidation<QC: Into<QueryConsolidation>>(
@@ -207,6 +269,29 @@ impl<'a, 'b, KeySpace, Handler> QueryingSubscriberBuilder<'a, 'b, KeySpace, Hand
         self.query_timeout = query_timeout;
         self
     }
+
+    /// Limit the number of samples per key expression retrieved by the queries.
+    ///
+    /// Only the `history` most recent replies of each key expression are merged with the live samples.
+    #[inline]
+    pub fn history(mut self, history: usize) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Re-run the initial query each time the session connects to a zenoh router,
+    /// in order to retrieve the publications missed while disconnected.
+    ///
+    /// The samples that were already received are not propagated again: for each key expression,
+    /// the replies that are not more recent than the last received sample are dropped.
+    ///
+    /// As the query is re-run in background, this requires the [`FetchingSubscriber`] to be
+    /// declared on an `Arc<Session>`.
+    #[inline]
+    pub fn fetch_on_reconnect(mut self, fetch_on_reconnect: bool) -> Self {
+        self.fetch_on_reconnect = fetch_on_reconnect;
+        self
+    }
 }
 
 impl<'a, KeySpace, Handler> Resolvable for QueryingSubscriberBuilder<'a, '_, KeySpace, Handler>
@@ -226,43 +311,43 @@ where
     fn res_sync(self) -> <Self as Resolvable>::To {
         let session = self.session.clone();
         let key_expr = self.key_expr?;
-        let key_space = self.key_space.clone().into();
-        let query_selector = match self.query_selector {
-            Some(s) => Some(s?),
-            None => None,
+        let query = FetchQuery {
+            key_space: self.key_space.clone().into(),
+            key_expr: key_expr.clone(),
+            selector: match self.query_selector {
+                Some(s) => Some(s?),
+                None => None,
+            },
+            target: self.query_target,
+            consolidation: self.query_consolidation,
+            accept_replies: self.query_accept_replies,
+            timeout: self.query_timeout,
+            history: self.history,
+            #[cfg(feature = "bundle")]
+            bundle: self.query_bundle,
         };
-        let query_target = self.query_target;
-        let query_consolidation = self.query_consolidation;
-        let query_accept_replies = self.query_accept_replies;
-        let query_timeout = self.query_timeout;
-        FetchingSubscriberBuilder {
+        let refetch: Option<Refetch> = match (self.fetch_on_reconnect, &self.session) {
+            (false, _) => None,
+            (true, SessionRef::Shared(session)) => {
+                let session = session.clone();
+                let query = query.clone().into_owned();
+                Some(Arc::new(move |cb| query.run(&session, cb)))
+            }
+            (true, SessionRef::Borrow(_)) => {
+                bail!("fetch_on_reconnect requires a subscriber declared on an Arc<Session>")
+            }
+        };
+        let conf = FetchingSubscriberBuilder {
             session: self.session,
-            key_expr: Ok(key_expr.clone()),
+            key_expr: Ok(key_expr),
             key_space: self.key_space,
             reliability: self.reliability,
             origin: self.origin,
-            fetch: |cb| match key_space {
-                crate::KeySpace::User => match query_selector {
-                    Some(s) => session.get(s),
-                    None => session.get(key_expr),
-                }
-                .callback(cb)
-                .target(query_target)
-                .consolidation(query_consolidation)
-                .accept_replies(query_accept_replies)
-                .timeout(query_timeout)
-                .res_sync(),
-                crate::KeySpace::Liveliness => session
-                    .liveliness()
-                    .get(key_expr)
-                    .callback(cb)
-                    .timeout(query_timeout)
-                    .res_sync(),
-            },
+            fetch: move |cb| query.run(&session, cb),
             handler: self.handler,
             phantom: std::marker::PhantomData,
-        }
-        .res_sync()
+        };
+        FetchingSubscriber::new(conf.with_static_keys(), refetch, self.on_gap)
     }
 }
 
@@ -279,6 +364,193 @@ where
     }
 }
 
+// The query performed by a QueryingSubscriber at startup,
+// and at each router reconnection if fetch_on_reconnect is set.
+#[derive(Clone)]
+struct FetchQuery<'b> {
+    key_space: crate::KeySpace,
+    key_expr: KeyExpr<'b>,
+    selector: Option<Selector<'b>>,
+    target: QueryTarget,
+    consolidation: QueryConsolidation,
+    accept_replies: ReplyKeyExpr,
+    timeout: Duration,
+    history: Option<usize>,
+    // whether the replies are asked as bundles
+    #[cfg(feature = "bundle")]
+    bundle: bool,
+}
+
+impl FetchQuery<'_> {
+    fn into_owned(self) -> FetchQuery<'static> {
+        FetchQuery {
+            key_space: self.key_space,
+            key_expr: self.key_expr.into_owned(),
+            selector: self.selector.map(Selector::into_owned),
+            target: self.target,
+            consolidation: self.consolidation,
+            accept_replies: self.accept_replies,
+            timeout: self.timeout,
+            history: self.history,
+            #[cfg(feature = "bundle")]
+            bundle: self.bundle,
+        }
+    }
+
+    fn run(&self, session: &Session, cb: Box<dyn Fn(Reply) + Send + Sync>) -> ZResult<()> {
+        let cb = match self.history {
+            Some(history) => HistoryLimiter::wrap(cb, history),
+            None => cb,
+        };
+        let selector = match &self.selector {
+            Some(s) => s.clone(),
+            None => self.key_expr.clone().into(),
+        };
+        // the bundles are unpacked before the history is limited
+        #[cfg(feature = "bundle")]
+        let (selector, cb) = if self.bundle {
+            let mut selector = selector;
+            selector.extend([(crate::ENCODING_KEY, crate::BUNDLE)]);
+            (selector, crate::bundle::unbundling(cb))
+        } else {
+            (selector, cb)
+        };
+        match self.key_space {
+            crate::KeySpace::User => session
+                .get(selector)
+                .callback(cb)
+                .target(self.target)
+                .consolidation(self.consolidation)
+                .accept_replies(self.accept_replies)
+                .timeout(self.timeout)
+                .res_sync(),
+            crate::KeySpace::Liveliness => session
+                .liveliness()
+                .get(self.key_expr.clone())
+                .callback(cb)
+                .timeout(self.timeout)
+                .res_sync(),
+        }
+    }
+}
+
+// Keeps only the `history` most recent replies of each key expression,
+// and forwards them to the wrapped callback once the query is complete (i.e. when dropped).
+// The replies without timestamp are considered as older than any reply with timestamp.
+struct HistoryLimiter {
+    callback: Box<dyn Fn(Reply) + Send + Sync>,
+    history: usize,
+    replies: Mutex<HashMap<OwnedKeyExpr, Vec<Reply>>>,
+}
+
+impl HistoryLimiter {
+    fn wrap(
+        callback: Box<dyn Fn(Reply) + Send + Sync>,
+        history: usize,
+    ) -> Box<dyn Fn(Reply) + Send + Sync> {
+        let limiter = HistoryLimiter {
+            callback,
+            history,
+            replies: Mutex::new(HashMap::new()),
+        };
+        Box::new(move |reply| limiter.push(reply))
+    }
+
+    fn push(&self, reply: Reply) {
+        let key_expr = match &reply.sample {
+            Ok(sample) => OwnedKeyExpr::from(sample.key_expr.clone()),
+            Err(_) => return (self.callback)(reply),
+        };
+        let mut replies = zlock!(self.replies);
+        let queue = replies.entry(key_expr).or_default();
+        queue.push(reply);
+        if queue.len() > self.history {
+            if let Some(oldest) = queue
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, r)| r.sample.as_ref().ok().and_then(|s| s.timestamp))
+                .map(|(i, _)| i)
+            {
+                queue.remove(oldest);
+            }
+        }
+    }
+}
+
+impl Drop for HistoryLimiter {
+    fn drop(&mut self) {
+        let replies = std::mem::take(&mut *zlock!(self.replies));
+        for reply in replies.into_values().flatten() {
+            (self.callback)(reply);
+        }
+    }
+}
+
+// The callback notified of the gaps between the replies and the live publications.
+type GapCallback = Arc<dyn Fn(Gap) + Send + Sync>;
+
+/// A possible gap between the replies to the queries of a [`FetchingSubscriber`]
+/// and its live publications on a key expression, notified to the
+/// [`on_gap`](QueryingSubscriberBuilder::on_gap) callback.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gap {
+    /// The key expression of the publications.
+    pub key_expr: OwnedKeyExpr,
+    /// The timestamp of the newest reply.
+    pub last_history_ts: Timestamp,
+    /// The timestamp of the oldest live publication received during the fetch.
+    pub first_live_ts: Timestamp,
+}
+
+// Tracks, for each key expression, the timestamps of the newest reply and of the oldest live
+// publication received during the fetches, to detect the gaps between them once merged.
+struct GapDetector {
+    callback: GapCallback,
+    // (newest reply, oldest live publication)
+    bounds: HashMap<OwnedKeyExpr, (Option<Timestamp>, Option<Timestamp>)>,
+}
+
+impl GapDetector {
+    fn fetched(&mut self, sample: &Sample) {
+        if let Some(ts) = sample.timestamp {
+            let (newest, _) = self.entry(sample);
+            *newest = Some(newest.map_or(ts, |newest| newest.max(ts)));
+        }
+    }
+
+    fn live(&mut self, sample: &Sample) {
+        if let Some(ts) = sample.timestamp {
+            let (_, oldest) = self.entry(sample);
+            *oldest = Some(oldest.map_or(ts, |oldest| oldest.min(ts)));
+        }
+    }
+
+    fn entry(&mut self, sample: &Sample) -> &mut (Option<Timestamp>, Option<Timestamp>) {
+        self.bounds
+            .entry(sample.key_expr.clone().into())
+            .or_default()
+    }
+
+    // Notifies the gaps detected since the last call, and resets the tracking.
+    fn flush(&mut self) {
+        for (key_expr, bounds) in self.bounds.drain() {
+            if let (Some(last_history_ts), Some(first_live_ts)) = bounds {
+                if last_history_ts < first_live_ts {
+                    (self.callback)(Gap {
+                        key_expr,
+                        last_history_ts,
+                        first_live_ts,
+                    });
+                }
+            }
+        }
+    }
+}
+
+// A fetch function that can be called several times.
+type Refetch = Arc<dyn Fn(Box<dyn Fn(Reply) + Send + Sync>) -> ZResult<()> + Send + Sync>;
+
 // Collects samples in their Timestamp order, if any,
 // and ignores repeating samples with duplicate timestamps.
 // Samples without Timestamps are kept in a separate Vector,
@@ -337,6 +609,32 @@ impl Iterator for MergeQueueValues {
 struct InnerState {
     pending_fetches: u64,
     merge_queue: MergeQueue,
+    // The timestamp of the last sample propagated for each key expression,
+    // only tracked if the fetch is re-run on router reconnection.
+    delivered: Option<HashMap<OwnedKeyExpr, Timestamp>>,
+    // only set if the gaps are notified
+    gap_detector: Option<GapDetector>,
+}
+
+impl InnerState {
+    // Records the sample as propagated. Returns false if a sample with
+    // a more recent or equal timestamp was already propagated for the same key expression.
+    fn mark_delivered(&mut self, sample: &Sample) -> bool {
+        match (&mut self.delivered, sample.timestamp) {
+            (Some(delivered), Some(ts)) => match delivered.get_mut(sample.key_expr.as_keyexpr()) {
+                Some(last) if *last >= ts => false,
+                Some(last) => {
+                    *last = ts;
+                    true
+                }
+                None => {
+                    delivered.insert(sample.key_expr.clone().into(), ts);
+                    true
+                }
+            },
+            _ => true,
+        }
+    }
 }
 
 /// The builder of [`FetchingSubscriber`], allowing to configure it.
@@ -558,7 +856,7 @@ where
     <TryIntoSample as TryInto<Sample>>::Error: Into<zenoh_core::Error>,
 {
     fn res_sync(self) -> <Self as Resolvable>::To {
-        FetchingSubscriber::new(self.with_static_keys())
+        FetchingSubscriber::new(self.with_static_keys(), None, None)
     }
 }
 
@@ -620,6 +918,7 @@ pub struct FetchingSubscriber<'a, Receiver> {
     callback: Arc<dyn Fn(Sample) + Send + Sync + 'static>,
     state: Arc<Mutex<InnerState>>,
     receiver: Receiver,
+    _refetch_stop: Option<flume::Sender<()>>,
 }
 
 impl<Receiver> std::ops::Deref for FetchingSubscriber<'_, Receiver> {
@@ -643,6 +942,8 @@ impl<'a, Receiver> FetchingSubscriber<'a, Receiver> {
         TryIntoSample,
     >(
         conf: FetchingSubscriberBuilder<'a, 'a, KeySpace, Handler, Fetch, TryIntoSample>,
+        refetch: Option<Refetch>,
+        on_gap: Option<GapCallback>,
     ) -> ZResult<Self>
     where
         KeySpace: Into<crate::KeySpace>,
@@ -653,6 +954,11 @@ impl<'a, Receiver> FetchingSubscriber<'a, Receiver> {
         let state = Arc::new(Mutex::new(InnerState {
             pending_fetches: 0,
             merge_queue: MergeQueue::new(),
+            delivered: refetch.as_ref().map(|_| HashMap::new()),
+            gap_detector: on_gap.map(|callback| GapDetector {
+                callback,
+                bounds: HashMap::new(),
+            }),
         }));
         let (callback, receiver) = conf.handler.into_cb_receiver_pair();
 
@@ -662,9 +968,13 @@ impl<'a, Receiver> FetchingSubscriber<'a, Receiver> {
             move |mut s| {
                 let state = &mut zlock!(state);
                 if state.pending_fetches == 0 {
+                    state.mark_delivered(&s);
                     callback(s);
                 } else {
                     log::trace!("Sample received while fetch in progress: push it to merge_queue");
+                    if let Some(gap_detector) = &mut state.gap_detector {
+                        gap_detector.live(&s);
+                    }
                     // ensure the sample has a timestamp, thus it will always be sorted into the MergeQueue
                     // after any timestamped Sample possibly coming from a fetch reply.
                     s.ensure_timestamp();
@@ -710,11 +1020,44 @@ impl<'a, Receiver> FetchingSubscriber<'a, Receiver> {
             callback,
             state,
             receiver,
+            _refetch_stop: None,
         };
 
         // start fetch
         fetch_subscriber.fetch(conf.fetch).res_sync()?;
 
+        // re-run the fetch at each router reconnection
+        if let Some(refetch) = refetch {
+            let (stop_tx, stop_rx) = flume::bounded::<()>(1);
+            let events = conf.session.info().routers_changes().res_sync();
+            let state = fetch_subscriber.state.clone();
+            let callback = fetch_subscriber.callback.clone();
+            async_std::task::spawn(async move {
+                loop {
+                    select!(
+                        event = events.recv_async().fuse() => match event {
+                            Ok(PeerEvent::Joined(zid)) => {
+                                log::debug!("Router {} connected: fetch again", zid);
+                                let fetch = FetchBuilder {
+                                    fetch: |cb| refetch(cb),
+                                    phantom: std::marker::PhantomData,
+                                    state: state.clone(),
+                                    callback: callback.clone(),
+                                };
+                                if let Err(e) = fetch.res_sync() {
+                                    log::warn!("Fetch on router reconnection failed: {}", e);
+                                }
+                            }
+                            Ok(PeerEvent::Left(_)) => (),
+                            Err(_) => return,
+                        },
+                        _ = stop_rx.recv_async().fuse() => return,
+                    )
+                }
+            });
+            fetch_subscriber._refetch_stop = Some(stop_tx);
+        }
+
         Ok(fetch_subscriber)
     }
 
@@ -808,8 +1151,13 @@ impl Drop for RepliesHandler {
                 "All fetches done. Replies and live publications merged - {} samples to propagate",
                 state.merge_queue.len()
             );
+            if let Some(gap_detector) = &mut state.gap_detector {
+                gap_detector.flush();
+            }
             for s in state.merge_queue.drain() {
-                (self.callback)(s);
+                if state.mark_delivered(&s) {
+                    (self.callback)(s);
+                }
             }
         }
     }
@@ -891,6 +1239,9 @@ where
             Ok(s) => {
                 let mut state = zlock!(handler.state);
                 log::trace!("Fetched sample received: push it to merge_queue");
+                if let Some(gap_detector) = &mut state.gap_detector {
+                    gap_detector.fetched(&s);
+                }
                 state.merge_queue.push(s);
             }
             Err(e) => log::debug!("Received error fetching data: {}", e.into()),
@@ -0,0 +1,161 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use std::convert::{TryFrom, TryInto};
+use zenoh::buffers::{SplitBuffer, ZBuf};
+use zenoh::prelude::{Encoding, OwnedKeyExpr, Sample, SampleKind, Value, ZenohId};
+use zenoh::sample::SourceInfo;
+use zenoh::time::{Timestamp, TimestampId, NTP64};
+
+// The binary encoding of the publications cached by a PublicationCache,
+// used to persist them, to export its state and to bundle its replies.
+//
+// Each publication is encoded as a record made of the length of its content as a u32,
+// followed by its content:
+//  - the key expression, the encoding and the payload, each as a u32 length followed by the bytes
+//  - the kind as a u8
+//  - 0u8 if there is no timestamp, or 1u8 followed by the timestamp's time as a u64
+//    and the timestamp's id as a u32 length followed by the bytes
+//  - if the publication has a source info, 0u8 if there is no source id or 1u8 followed by
+//    the source id as a u32 length followed by the bytes, and then 0u8 if there is no source
+//    sequence number or 1u8 followed by the source sequence number as a u64
+// All integers are little-endian. The source info is omitted when it's empty, so that records
+// written before it was encoded are still decoded.
+
+pub(crate) fn encode_record(sample: &Sample) -> Vec<u8> {
+    let mut content = Vec::new();
+    write_sized(&mut content, sample.key_expr.as_str().as_bytes());
+    write_sized(&mut content, sample.value.encoding.to_string().as_bytes());
+    write_sized(&mut content, &sample.value.payload.contiguous());
+    content.push(sample.kind as u8);
+    match &sample.timestamp {
+        None => content.push(0),
+        Some(timestamp) => {
+            content.push(1);
+            content.extend_from_slice(&timestamp.get_time().as_u64().to_le_bytes());
+            let id = timestamp.get_id();
+            write_sized(&mut content, &id.to_le_bytes()[..id.size()]);
+        }
+    }
+    let source_info = &sample.source_info;
+    if source_info.source_id.is_some() || source_info.source_sn.is_some() {
+        match &source_info.source_id {
+            None => content.push(0),
+            Some(id) => {
+                content.push(1);
+                write_sized(&mut content, &id.to_le_bytes()[..id.size()]);
+            }
+        }
+        match source_info.source_sn {
+            None => content.push(0),
+            Some(sn) => {
+                content.push(1);
+                content.extend_from_slice(&sn.to_le_bytes());
+            }
+        }
+    }
+    let mut record = Vec::with_capacity(4 + content.len());
+    record.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    record.extend_from_slice(&content);
+    record
+}
+
+fn write_sized(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+// Decodes the record at the start of `bytes`, returning the publication and the length of the record.
+// Returns None if `bytes` doesn't start with a complete and valid record.
+pub(crate) fn decode_record(bytes: &[u8]) -> Option<(Sample, usize)> {
+    let mut reader = Reader(bytes);
+    let len = reader.read_u32()? as usize;
+    let mut content = Reader(reader.read(len)?);
+
+    let key_expr = std::str::from_utf8(content.read_sized()?).ok()?;
+    let key_expr = OwnedKeyExpr::try_from(key_expr.to_string()).ok()?;
+    let encoding = std::str::from_utf8(content.read_sized()?).ok()?;
+    let encoding = Encoding::from(encoding.to_string());
+    let payload = ZBuf::from(content.read_sized()?.to_vec());
+    let kind = SampleKind::try_from(content.read_u8()? as u64).ok()?;
+    let timestamp = match content.read_u8()? {
+        0 => None,
+        1 => {
+            let time = NTP64(content.read_u64()?);
+            let id = TimestampId::try_from(content.read_sized()?).ok()?;
+            Some(Timestamp::new(time, id))
+        }
+        _ => return None,
+    };
+    let source_info = if content.0.is_empty() {
+        None
+    } else {
+        let source_id = match content.read_u8()? {
+            0 => None,
+            1 => Some(ZenohId::try_from(content.read_sized()?).ok()?),
+            _ => return None,
+        };
+        let source_sn = match content.read_u8()? {
+            0 => None,
+            1 => Some(content.read_u64()?),
+            _ => return None,
+        };
+        Some(SourceInfo {
+            source_id,
+            source_sn,
+        })
+    };
+    if !content.0.is_empty() {
+        return None;
+    }
+
+    let mut sample = Sample::new(key_expr, Value::new(payload).encoding(encoding));
+    sample.kind = kind;
+    if let Some(timestamp) = timestamp {
+        sample = sample.with_timestamp(timestamp);
+    }
+    if let Some(source_info) = source_info {
+        sample = sample.with_source_info(source_info);
+    }
+    Some((sample, 4 + len))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, remaining) = self.0.split_at(len);
+        self.0 = remaining;
+        Some(bytes)
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        self.read(1).map(|bytes| bytes[0])
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        self.read(4)?.try_into().ok().map(u32::from_le_bytes)
+    }
+
+    fn read_u64(&mut self) -> Option<u64> {
+        self.read(8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+
+    fn read_sized(&mut self) -> Option<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.read(len)
+    }
+}
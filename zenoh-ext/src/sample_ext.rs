@@ -0,0 +1,70 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use serde::de::DeserializeOwned;
+use zenoh::buffers::SplitBuffer;
+use zenoh::prelude::{Encoding, KnownEncoding, Sample};
+use zenoh_result::{zerror, ZResult};
+
+/// The encoding of the CBOR payloads.
+pub const APP_CBOR: &str = "application/cbor";
+
+/// Some extensions to the [`zenoh::prelude::Sample`](zenoh::prelude::Sample)
+pub trait SampleExt {
+    /// Deserialize the payload of this sample, with the decoder selected by its encoding.
+    ///
+    /// The supported encodings are `application/json` and `text/json` (decoded as JSON),
+    /// and `application/cbor` (decoded as CBOR, see [`APP_CBOR`]).
+    ///
+    /// # Examples
+    /// ```
+    /// use zenoh::prelude::r#async::*;
+    /// use zenoh_ext::SampleExt;
+    ///
+    /// let sample = Sample::new(
+    ///     KeyExpr::try_from("key/expression").unwrap(),
+    ///     Value::from(r#"{"temperature": 21.5}"#).encoding(KnownEncoding::AppJson.into()),
+    /// );
+    /// let value: std::collections::HashMap<String, f64> = sample.try_into_value().unwrap();
+    /// assert_eq!(value["temperature"], 21.5);
+    /// ```
+    fn try_into_value<T: DeserializeOwned>(&self) -> ZResult<T>;
+}
+
+impl SampleExt for Sample {
+    fn try_into_value<T: DeserializeOwned>(&self) -> ZResult<T> {
+        let encoding = &self.value.encoding;
+        let payload = self.value.payload.contiguous();
+        if *encoding == Encoding::Exact(KnownEncoding::AppJson)
+            || *encoding == Encoding::Exact(KnownEncoding::TextJson)
+        {
+            serde_json::from_slice(&payload).map_err(|e| {
+                zerror!("Failed to decode JSON payload of {}: {}", self.key_expr, e).into()
+            })
+        } else if encoding.to_string() == APP_CBOR {
+            ciborium::de::from_reader(&*payload).map_err(|e| {
+                zerror!("Failed to decode CBOR payload of {}: {}", self.key_expr, e).into()
+            })
+        } else {
+            Err(zerror!(
+                "Unsupported encoding {} for the payload of {}: expected {}, {} or {}",
+                encoding,
+                self.key_expr,
+                Encoding::APP_JSON,
+                Encoding::TEXT_JSON,
+                APP_CBOR
+            )
+            .into())
+        }
+    }
+}
@@ -11,11 +11,38 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
-use super::PublicationCacheBuilder;
+use super::{AdvancedPublisherBuilder, GetHistoryBuilder, PublicationCacheBuilder};
 use std::convert::TryInto;
 use std::sync::Arc;
-use zenoh::prelude::KeyExpr;
-use zenoh::Session;
+use zenoh::config::{Config, ModeDependentValue};
+use zenoh::prelude::{KeyExpr, Selector};
+use zenoh::{OpenBuilder, Session};
+
+/// Open a zenoh [`Session`] with a copy of the given configuration, in which the timestamping
+/// of the publications is enabled (i.e. `timestamping/enabled` is overridden with `true`).
+///
+/// This is required by the [`PublicationCache`](crate::PublicationCache)s, unless declared with
+/// [`require_timestamps(false)`](crate::PublicationCacheBuilder::require_timestamps), and
+/// avoids the [`MissingHlc`](crate::PublicationCacheError::MissingHlc) error when declaring them.
+///
+/// # Examples
+/// ```no_run
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+/// use zenoh_ext::*;
+///
+/// let session = zenoh_ext::open_with_cache_support(&config::peer()).res().await.unwrap();
+/// let cache = session.declare_publication_cache("key/expression").res().await.unwrap();
+/// # })
+/// ```
+pub fn open_with_cache_support(config: &Config) -> OpenBuilder<Config> {
+    let mut config = config.clone();
+    // timestamping/enabled has no validation, thus setting it can't fail
+    let _ = config
+        .timestamping
+        .set_enabled(Some(ModeDependentValue::Unique(true)));
+    zenoh::open(config)
+}
 
 /// Some extensions to the [`zenoh::Session`](zenoh::Session)
 pub trait SessionExt {
@@ -26,6 +53,42 @@ pub trait SessionExt {
     where
         TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
         <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>;
+
+    /// Declare a [`PublicationCache`](crate::PublicationCache) mirroring the whole subtree
+    /// under the given prefix, i.e. caching the last publication on each key expression
+    /// matching `<prefix>/**`.
+    ///
+    /// This is a preset of [`declare_publication_cache`](SessionExt::declare_publication_cache)
+    /// that can be further configured with the returned builder.
+    fn cache_subtree<'a, 'b, 'c, TryIntoKeyExpr>(
+        &'a self,
+        prefix: TryIntoKeyExpr,
+    ) -> PublicationCacheBuilder<'a, 'b, 'c>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>;
+
+    /// Declare an [`AdvancedPublisher`](crate::AdvancedPublisher), i.e. a publisher with
+    /// a [`PublicationCache`](crate::PublicationCache) of its publications.
+    fn declare_advanced_publisher<'a, 'b, 'c, TryIntoKeyExpr>(
+        &'a self,
+        key_expr: TryIntoKeyExpr,
+    ) -> AdvancedPublisherBuilder<'a, 'b, 'c>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>;
+
+    /// Query the history of the publications matching the given selector,
+    /// typically from [`PublicationCache`](crate::PublicationCache)s.
+    ///
+    /// See [`GetHistoryBuilder`] for the post-processing of the replies.
+    fn get_history<'a, 'b, IntoSelector>(
+        &'a self,
+        selector: IntoSelector,
+    ) -> GetHistoryBuilder<'a, 'b>
+    where
+        IntoSelector: TryInto<Selector<'b>>,
+        <IntoSelector as TryInto<Selector<'b>>>::Error: Into<zenoh_result::Error>;
 }
 
 impl SessionExt for Session {
@@ -39,6 +102,49 @@ impl SessionExt for Session {
     {
         PublicationCacheBuilder::new(self, pub_key_expr.try_into().map_err(Into::into))
     }
+
+    fn cache_subtree<'a, 'b, 'c, TryIntoKeyExpr>(
+        &'a self,
+        prefix: TryIntoKeyExpr,
+    ) -> PublicationCacheBuilder<'a, 'b, 'c>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        let key_expr = prefix
+            .try_into()
+            .map_err(Into::into)
+            .and_then(|prefix| prefix.join("**"));
+        PublicationCacheBuilder::new(self, key_expr).history(1)
+    }
+
+    fn declare_advanced_publisher<'a, 'b, 'c, TryIntoKeyExpr>(
+        &'a self,
+        key_expr: TryIntoKeyExpr,
+    ) -> AdvancedPublisherBuilder<'a, 'b, 'c>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        AdvancedPublisherBuilder::new(self, key_expr.try_into().map_err(Into::into))
+    }
+
+    fn get_history<'a, 'b, IntoSelector>(
+        &'a self,
+        selector: IntoSelector,
+    ) -> GetHistoryBuilder<'a, 'b>
+    where
+        IntoSelector: TryInto<Selector<'b>>,
+        <IntoSelector as TryInto<Selector<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        GetHistoryBuilder {
+            session: self,
+            selector: selector.try_into().map_err(Into::into),
+            history: None,
+            consolidate: false,
+            timeout: None,
+        }
+    }
 }
 
 impl SessionExt for Arc<Session> {
@@ -52,4 +158,47 @@ impl SessionExt for Arc<Session> {
     {
         PublicationCacheBuilder::new(self, pub_key_expr.try_into().map_err(Into::into))
     }
+
+    fn cache_subtree<'a, 'b, 'c, TryIntoKeyExpr>(
+        &'a self,
+        prefix: TryIntoKeyExpr,
+    ) -> PublicationCacheBuilder<'a, 'b, 'c>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        let key_expr = prefix
+            .try_into()
+            .map_err(Into::into)
+            .and_then(|prefix| prefix.join("**"));
+        PublicationCacheBuilder::new(self, key_expr).history(1)
+    }
+
+    fn declare_advanced_publisher<'a, 'b, 'c, TryIntoKeyExpr>(
+        &'a self,
+        key_expr: TryIntoKeyExpr,
+    ) -> AdvancedPublisherBuilder<'a, 'b, 'c>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        AdvancedPublisherBuilder::new(self, key_expr.try_into().map_err(Into::into))
+    }
+
+    fn get_history<'a, 'b, IntoSelector>(
+        &'a self,
+        selector: IntoSelector,
+    ) -> GetHistoryBuilder<'a, 'b>
+    where
+        IntoSelector: TryInto<Selector<'b>>,
+        <IntoSelector as TryInto<Selector<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        GetHistoryBuilder {
+            session: self,
+            selector: selector.try_into().map_err(Into::into),
+            history: None,
+            consolidate: false,
+            timeout: None,
+        }
+    }
 }
@@ -228,6 +228,11 @@ impl<'a, 'b, Handler> SubscriberBuilderExt<'a, 'b, Handler>
             query_consolidation: QueryConsolidation::from(zenoh::query::ConsolidationMode::None),
             query_accept_replies: ReplyKeyExpr::default(),
             query_timeout: Duration::from_secs(10),
+            history: None,
+            fetch_on_reconnect: false,
+            #[cfg(feature = "bundle")]
+            query_bundle: false,
+            on_gap: None,
             handler: self.handler,
         }
     }
@@ -341,6 +346,11 @@ impl<'a, 'b, Handler> SubscriberBuilderExt<'a, 'b, Handler>
             query_consolidation: QueryConsolidation::default(),
             query_accept_replies: ReplyKeyExpr::MatchingQuery,
             query_timeout: Duration::from_secs(10),
+            history: None,
+            fetch_on_reconnect: false,
+            #[cfg(feature = "bundle")]
+            query_bundle: false,
+            on_gap: None,
             handler: self.handler,
         }
     }
@@ -0,0 +1,1272 @@
+//
+// Copyright (c) 2023 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use async_std::prelude::FutureExt;
+use async_std::task;
+use std::convert::TryFrom;
+use std::time::Duration;
+use zenoh::config::ModeDependentValue;
+use zenoh::prelude::r#async::*;
+use zenoh::sample::SourceInfo;
+use zenoh_ext::*;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+const SLEEP: Duration = Duration::from_secs(1);
+
+macro_rules! ztimeout {
+    ($f:expr) => {
+        $f.timeout(TIMEOUT).await.unwrap()
+    };
+}
+
+async fn open_session(endpoints: &[&str]) -> (Session, Session) {
+    // Open the sessions, with timestamping enabled as required by PublicationCache
+    let mut config = config::peer();
+    config.listen.endpoints = endpoints
+        .iter()
+        .map(|e| e.parse().unwrap())
+        .collect::<Vec<_>>();
+    config.scouting.multicast.set_enabled(Some(false)).unwrap();
+    config
+        .timestamping
+        .set_enabled(Some(ModeDependentValue::Unique(true)))
+        .unwrap();
+    println!("[  ][01a] Opening peer01 session");
+    let peer01 = ztimeout!(zenoh::open(config).res_async()).unwrap();
+
+    let mut config = config::peer();
+    config.connect.endpoints = endpoints
+        .iter()
+        .map(|e| e.parse().unwrap())
+        .collect::<Vec<_>>();
+    config.scouting.multicast.set_enabled(Some(false)).unwrap();
+    println!("[  ][02a] Opening peer02 session");
+    let peer02 = ztimeout!(zenoh::open(config).res_async()).unwrap();
+
+    (peer01, peer02)
+}
+
+async fn close_session(peer01: Session, peer02: Session) {
+    println!("[  ][01d] Closing peer01 session");
+    ztimeout!(peer01.close().res_async()).unwrap();
+    println!("[  ][02d] Closing peer02 session");
+    ztimeout!(peer02.close().res_async()).unwrap();
+}
+
+// Queries the cache, returning the replied samples.
+async fn get(session: &Session, selector: &str) -> Vec<Sample> {
+    let replies = ztimeout!(session
+        .get(selector)
+        .consolidation(ConsolidationMode::None)
+        .res_async())
+    .unwrap();
+    let mut samples = Vec::new();
+    while let Ok(reply) = ztimeout!(replies.recv_async()) {
+        samples.push(reply.sample.unwrap());
+    }
+    samples
+}
+
+#[test]
+fn publication_cache_preserves_sample_metadata() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17480"]).await;
+
+        // Seed the cache with a sample carrying source infos, which can't be set on a put
+        let source_id = peer01.zid();
+        let seeded = Sample::new(
+            KeyExpr::try_from("test/pubcache/metadata/seeded").unwrap(),
+            Value::from("seeded").encoding(KnownEncoding::AppCustom.into()),
+        )
+        .with_timestamp(peer01.hlc().unwrap().new_timestamp())
+        .with_source_info(SourceInfo {
+            source_id: Some(source_id),
+            source_sn: Some(42),
+        });
+
+        println!("[PC][01b] Declaring PublicationCache on peer01 session");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/metadata/**")
+            .initial_samples(vec![seeded.clone()])
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing on peer01 session");
+        ztimeout!(peer01
+            .put("test/pubcache/metadata/put", "put")
+            .encoding(KnownEncoding::TextJson)
+            .res_async())
+        .unwrap();
+        ztimeout!(peer01.delete("test/pubcache/metadata/delete").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+
+        let cached = ztimeout!(cache.snapshot().res_async()).unwrap();
+        assert_eq!(cached.samples_count, 3);
+
+        println!("[PC][03b] Querying the PublicationCache from peer02 session");
+        let replies = get(&peer02, "test/pubcache/metadata/**").await;
+        assert_eq!(replies.len(), 3);
+        for reply in &replies {
+            let expected = &cached[&OwnedKeyExpr::from(reply.key_expr.clone())][0];
+            assert_eq!(
+                reply.value.payload.contiguous(),
+                expected.value.payload.contiguous()
+            );
+            assert_eq!(reply.value.encoding, expected.value.encoding);
+            assert_eq!(reply.kind, expected.kind);
+            assert!(reply.timestamp.is_some());
+            assert_eq!(reply.timestamp, expected.timestamp);
+        }
+        let reply_on = |key_expr: &str| {
+            replies
+                .iter()
+                .find(|reply| reply.key_expr.as_str() == key_expr)
+                .unwrap()
+        };
+        let reply = reply_on("test/pubcache/metadata/seeded");
+        assert_eq!(reply.value.encoding, seeded.value.encoding);
+        assert_eq!(reply.timestamp, seeded.timestamp);
+        assert_eq!(reply.source_info.source_id, Some(source_id));
+        assert_eq!(reply.source_info.source_sn, Some(42));
+        let reply = reply_on("test/pubcache/metadata/put");
+        assert_eq!(reply.value.encoding, KnownEncoding::TextJson.into());
+        let reply = reply_on("test/pubcache/metadata/delete");
+        assert_eq!(reply.kind, SampleKind::Delete);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_concrete_query_matches_wildcard_keys() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17481"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache on peer01 session");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/wild/**")
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing on a wildcard and a concrete key expression");
+        ztimeout!(peer01.put("test/pubcache/wild/*/x", "wild").res_async()).unwrap();
+        ztimeout!(peer01.put("test/pubcache/wild/b/y", "concrete").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+
+        println!("[PC][03b] Querying concrete key expressions from peer02 session");
+        let replies = get(&peer02, "test/pubcache/wild/a/x").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].key_expr.as_str(), "test/pubcache/wild/*/x");
+        let replies = get(&peer02, "test/pubcache/wild/b/x").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].key_expr.as_str(), "test/pubcache/wild/*/x");
+        let replies = get(&peer02, "test/pubcache/wild/b/y").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].key_expr.as_str(), "test/pubcache/wild/b/y");
+        let replies = get(&peer02, "test/pubcache/wild/a/y").await;
+        assert!(replies.is_empty());
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_zero_history() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17482"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with a history of 0");
+        let builder = peer01
+            .declare_publication_cache("test/pubcache/zero/**")
+            .history(0);
+        assert!(matches!(
+            builder
+                .validate()
+                .unwrap_err()
+                .downcast_ref::<PublicationCacheError>(),
+            Some(PublicationCacheError::ZeroHistory)
+        ));
+        match ztimeout!(builder.res_async()) {
+            Err(e) => assert!(matches!(
+                e.downcast_ref::<PublicationCacheError>(),
+                Some(PublicationCacheError::ZeroHistory)
+            )),
+            Ok(_) => panic!("PublicationCache declared with a history of 0"),
+        }
+
+        println!("[PC][02b] Declaring PublicationCache with a history of 0 for some resources");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/zero/**")
+            .history(|key_expr: &keyexpr| if key_expr.ends_with("/none") { 0 } else { 2 })
+            .res_async())
+        .unwrap();
+        for i in 0..3 {
+            ztimeout!(peer01
+                .put("test/pubcache/zero/none", i.to_string())
+                .res_async())
+            .unwrap();
+            ztimeout!(peer01
+                .put("test/pubcache/zero/some", i.to_string())
+                .res_async())
+            .unwrap();
+        }
+        task::sleep(SLEEP).await;
+
+        let replies = get(&peer02, "test/pubcache/zero/**").await;
+        assert_eq!(replies.len(), 2);
+        assert!(replies
+            .iter()
+            .all(|reply| reply.key_expr.as_str() == "test/pubcache/zero/some"));
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_rejects_sample_larger_than_size_limit() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17483"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with a size limit of 16 bytes");
+        let (evicted_tx, evicted_rx) = flume::unbounded();
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/size/**")
+            .history(4)
+            .size_limit(16)
+            .on_evict(move |evicted| {
+                let _ = evicted_tx.send(evicted);
+            })
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing small samples and one larger than the size limit");
+        ztimeout!(peer01.put("test/pubcache/size/small", "0123").res_async()).unwrap();
+        ztimeout!(peer01.put("test/pubcache/size/small", "4567").res_async()).unwrap();
+        ztimeout!(peer01
+            .put("test/pubcache/size/big", vec![0u8; 1024])
+            .res_async())
+        .unwrap();
+        task::sleep(SLEEP).await;
+
+        let evicted: Vec<EvictedSample> = evicted_rx.try_iter().collect();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].key_expr.as_str(), "test/pubcache/size/big");
+        assert_eq!(evicted[0].reason, EvictionReason::TooLarge);
+        let stats = cache.stats();
+        assert_eq!(stats.evicted_too_large, 1);
+        assert_eq!(stats.evicted_size_limit, 0);
+
+        println!("[PC][03b] Querying the PublicationCache from peer02 session");
+        let replies = get(&peer02, "test/pubcache/size/**").await;
+        assert_eq!(replies.len(), 2);
+        assert!(replies
+            .iter()
+            .all(|reply| reply.key_expr.as_str() == "test/pubcache/size/small"));
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_clear() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17484"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache on peer01 session");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/clear/**")
+            .history(4)
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing on peer01 session");
+        for i in 0..3 {
+            ztimeout!(peer01
+                .put("test/pubcache/clear/a", i.to_string())
+                .res_async())
+            .unwrap();
+            ztimeout!(peer01
+                .put("test/pubcache/clear/b", i.to_string())
+                .res_async())
+            .unwrap();
+        }
+        task::sleep(SLEEP).await;
+        assert_eq!(get(&peer02, "test/pubcache/clear/**").await.len(), 6);
+        assert_eq!(cache.stats().samples_cached, 6);
+
+        println!("[PC][03b] Clearing the PublicationCache");
+        ztimeout!(cache.clear().res_async()).unwrap();
+        let cached = ztimeout!(cache.snapshot().res_async()).unwrap();
+        assert_eq!(cached.samples_count, 0);
+        assert_eq!(cache.stats(), CacheStats::default());
+        assert!(get(&peer02, "test/pubcache/clear/**").await.is_empty());
+
+        println!("[PC][04b] Publishing again on peer01 session");
+        ztimeout!(peer01.put("test/pubcache/clear/a", "new").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        let replies = get(&peer02, "test/pubcache/clear/**").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].key_expr.as_str(), "test/pubcache/clear/a");
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_equal_timestamps_in_fifo_order() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17485"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with samples sharing a timestamp");
+        let timestamp = zenoh::time::new_reception_timestamp();
+        let samples: Vec<Sample> = ["c", "a", "b", "a", "c"]
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                Sample::new(
+                    keyexpr::new("test/pubcache/ties")
+                        .unwrap()
+                        .join(key)
+                        .unwrap(),
+                    i.to_string(),
+                )
+                .with_timestamp(timestamp)
+            })
+            .collect();
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/ties/*")
+            .history(2)
+            .reply_ordering(ReplyOrdering::ByTimestamp)
+            .initial_samples(samples)
+            .res_async())
+        .unwrap();
+        task::sleep(SLEEP).await;
+
+        println!("[PC][02b] Querying the PublicationCache from peer02 session");
+        let replies = get(&peer02, "test/pubcache/ties/*").await;
+        let values: Vec<String> = replies
+            .iter()
+            .map(|reply| reply.value.to_string())
+            .collect();
+        assert_eq!(values, ["0", "1", "2", "3", "4"]);
+
+        println!("[PC][03b] Querying a single resource with a time range");
+        let replies = get(&peer02, "test/pubcache/ties/c?_time=[..]").await;
+        let values: Vec<String> = replies
+            .iter()
+            .map(|reply| reply.value.to_string())
+            .collect();
+        assert_eq!(values, ["0", "4"]);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_latest_consolidation_out_of_order() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17479"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with a sample older than the previous one");
+        let hlc = peer01.hlc().unwrap();
+        let older = hlc.new_timestamp();
+        let newer = hlc.new_timestamp();
+        let key_expr = "test/pubcache/latest";
+        let cache = ztimeout!(peer01
+            .declare_publication_cache(key_expr)
+            .history(4)
+            .query_consolidation(ConsolidationMode::Latest)
+            .initial_samples(vec![
+                Sample::new(KeyExpr::try_from(key_expr).unwrap(), "newer").with_timestamp(newer),
+                Sample::new(KeyExpr::try_from(key_expr).unwrap(), "older").with_timestamp(older),
+            ])
+            .res_async())
+        .unwrap();
+        task::sleep(SLEEP).await;
+
+        println!("[PC][02b] Querying the PublicationCache from peer02 session");
+        let replies = get(&peer02, key_expr).await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].value.to_string(), "newer");
+        assert_eq!(replies[0].timestamp, Some(newer));
+
+        println!("[PC][03b] Querying the keys only");
+        let replies = get(&peer02, &format!("{key_expr}?_keys_only=true")).await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].timestamp, Some(newer));
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_stores_canonical_keys() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17486"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with an initial sample");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/canon/**")
+            .add_queryable_prefix("test/prefix")
+            .strip_prefix_on_reply(false)
+            .initial_samples(vec![Sample::new(
+                KeyExpr::try_from("test/pubcache/canon/init").unwrap(),
+                "init",
+            )])
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing on concrete and wildcard keys");
+        for key_expr in [
+            "test/pubcache/canon/a/b",
+            "test/pubcache/canon/a/*/c",
+            "test/pubcache/canon/a/**/d",
+        ] {
+            ztimeout!(peer01.put(key_expr, "value").res_async()).unwrap();
+        }
+        // non canonical keys are rejected before reaching the cache
+        assert!(KeyExpr::try_from("test/pubcache/canon/**/**").is_err());
+        assert!(KeyExpr::try_from("test/pubcache/canon/a//b").is_err());
+        task::sleep(SLEEP).await;
+
+        println!("[PC][03b] Checking that the cached keys are canonical");
+        let keys = ztimeout!(cache.cached_keys().res_async()).unwrap();
+        assert_eq!(keys.len(), 4);
+        for key in &keys {
+            assert!(keyexpr::new(key.as_str()).is_ok());
+            assert_eq!(
+                &OwnedKeyExpr::autocanonize(key.to_string()).unwrap(),
+                key,
+                "{key} is not canonical"
+            );
+        }
+
+        println!("[PC][04b] Querying the cached keys with wildcards");
+        assert_eq!(
+            get(&peer02, "test/prefix/test/pubcache/canon/**")
+                .await
+                .len(),
+            4
+        );
+        assert_eq!(get(&peer02, "test/prefix/**/canon/a/*/c").await.len(), 1);
+        assert_eq!(
+            get(&peer02, "test/prefix/test/pubcache/canon/a/x/c")
+                .await
+                .len(),
+            1
+        );
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn advanced_publisher_caches_its_publications() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17487"]).await;
+
+        println!("[PC][01b] Declaring AdvancedPublisher");
+        let publisher = ztimeout!(peer01
+            .declare_advanced_publisher("test/advanced/pub")
+            .cache_history(2)
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing through the AdvancedPublisher");
+        for i in 0..3 {
+            ztimeout!(publisher.put(format!("value{i}")).res_async()).unwrap();
+        }
+        task::sleep(SLEEP).await;
+
+        println!("[PC][03b] Querying the history of the AdvancedPublisher");
+        let samples = get(&peer02, "test/advanced/pub").await;
+        let values: Vec<String> = samples.iter().map(|s| s.value.to_string()).collect();
+        assert_eq!(values, ["value1", "value2"]);
+
+        println!("[PC][04b] Deleting through the AdvancedPublisher");
+        ztimeout!(publisher.delete().res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        let samples = get(&peer02, "test/advanced/pub").await;
+        assert_eq!(samples.last().unwrap().kind, SampleKind::Delete);
+
+        ztimeout!(publisher.close().res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        assert!(get(&peer02, "test/advanced/pub").await.is_empty());
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_queryable_prefix() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17488"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with a wildcard queryable_prefix");
+        for prefix in ["test/*/prefix", "test/**"] {
+            let builder = peer01
+                .declare_publication_cache("test/pubcache/prefix/**")
+                .queryable_prefix(prefix);
+            assert!(matches!(
+                builder
+                    .validate()
+                    .unwrap_err()
+                    .downcast_ref::<PublicationCacheError>(),
+                Some(PublicationCacheError::InvalidQueryablePrefix(_))
+            ));
+            match ztimeout!(builder.res_async()) {
+                Err(e) => assert!(matches!(
+                    e.downcast_ref::<PublicationCacheError>(),
+                    Some(PublicationCacheError::InvalidQueryablePrefix(_))
+                )),
+                Ok(_) => panic!("PublicationCache declared with queryable_prefix {prefix}"),
+            }
+        }
+
+        println!("[PC][02b] Declaring PublicationCache with a concrete queryable_prefix");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/prefix/**")
+            .queryable_prefix("test/concrete")
+            .strip_prefix_on_reply(false)
+            .res_async())
+        .unwrap();
+        ztimeout!(peer01.put("test/pubcache/prefix/a", "a").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+
+        let replies = get(&peer02, "test/concrete/test/pubcache/prefix/*").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(
+            replies[0].key_expr.as_str(),
+            "test/concrete/test/pubcache/prefix/a"
+        );
+        assert!(get(&peer02, "test/pubcache/prefix/*").await.is_empty());
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_time_expiration() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17489"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with a time expiration of 2s");
+        let (evicted_tx, evicted_rx) = flume::unbounded();
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/ttl/**")
+            .history(4)
+            .time_expiration(Duration::from_secs(2))
+            .on_evict(move |evicted| {
+                let _ = evicted_tx.send(evicted);
+            })
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing an old and a newer sample");
+        ztimeout!(peer01.put("test/pubcache/ttl/a", "old").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        ztimeout!(peer01.put("test/pubcache/ttl/a", "new").res_async()).unwrap();
+        task::sleep(SLEEP / 2).await;
+        assert_eq!(get(&peer02, "test/pubcache/ttl/a").await.len(), 2);
+
+        println!("[PC][03b] Querying once the old sample expired");
+        task::sleep(SLEEP).await;
+        let replies = get(&peer02, "test/pubcache/ttl/a").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].value.to_string(), "new");
+
+        println!("[PC][04b] Querying once both samples expired");
+        task::sleep(SLEEP * 2).await;
+        assert!(get(&peer02, "test/pubcache/ttl/a").await.is_empty());
+        let evicted: Vec<EvictedSample> = evicted_rx.try_iter().collect();
+        assert_eq!(evicted.len(), 2);
+        assert!(evicted
+            .iter()
+            .all(|evicted| evicted.reason == EvictionReason::Expired));
+        assert_eq!(cache.stats().evicted_expired, 2);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_size_limit_evicts_oldest() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17490"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache with a size limit of 10 bytes");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/sizelimit/**")
+            .history(4)
+            .size_limit(10)
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing more than the size limit");
+        for (key, value) in [("a", "0123"), ("b", "4567"), ("a", "89ab")] {
+            ztimeout!(peer01
+                .put(format!("test/pubcache/sizelimit/{key}"), value)
+                .res_async())
+            .unwrap();
+        }
+        task::sleep(SLEEP).await;
+
+        println!("[PC][03b] Checking that the oldest publication was evicted");
+        let mut values: Vec<String> = get(&peer02, "test/pubcache/sizelimit/**")
+            .await
+            .iter()
+            .map(|sample| sample.value.to_string())
+            .collect();
+        values.sort();
+        assert_eq!(values, ["4567", "89ab"]);
+        assert_eq!(cache.stats().evicted_size_limit, 1);
+
+        println!("[PC][04b] Publishing a sample filling the whole size limit");
+        ztimeout!(peer01
+            .put("test/pubcache/sizelimit/c", "0123456789")
+            .res_async())
+        .unwrap();
+        task::sleep(SLEEP).await;
+        let replies = get(&peer02, "test/pubcache/sizelimit/**").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].key_expr.as_str(), "test/pubcache/sizelimit/c");
+        assert_eq!(cache.stats().evicted_size_limit, 3);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[cfg(feature = "persistence")]
+#[test]
+fn publication_cache_persistence_reload() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17491"]).await;
+        let path = std::env::temp_dir().join(format!("zenoh-pubcache-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        println!(
+            "[PC][01b] Declaring PublicationCache persisted to {:?}",
+            path
+        );
+        let seeded = Sample::new(
+            KeyExpr::try_from("test/pubcache/persist/c").unwrap(),
+            "seeded",
+        )
+        .with_timestamp(peer01.hlc().unwrap().new_timestamp())
+        .with_source_info(SourceInfo {
+            source_id: Some(peer01.zid()),
+            source_sn: Some(7),
+        });
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/persist/**")
+            .history(2)
+            .persistence(&path)
+            .initial_samples(vec![seeded])
+            .res_async())
+        .unwrap();
+        for i in 0..4 {
+            ztimeout!(peer01
+                .put("test/pubcache/persist/a", i.to_string())
+                .res_async())
+            .unwrap();
+        }
+        ztimeout!(peer01.delete("test/pubcache/persist/b").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        // the resources are replied in no particular order
+        let mut before = get(&peer02, "test/pubcache/persist/**").await;
+        before.sort_by_key(|sample| sample.timestamp);
+        assert_eq!(before.len(), 4);
+        ztimeout!(cache.close().res_async()).unwrap();
+
+        println!(
+            "[PC][02b] Declaring a new PublicationCache reloading {:?}",
+            path
+        );
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/persist/**")
+            .history(2)
+            .persistence(&path)
+            .res_async())
+        .unwrap();
+        task::sleep(SLEEP).await;
+        let mut after = get(&peer02, "test/pubcache/persist/**").await;
+        after.sort_by_key(|sample| sample.timestamp);
+        assert_eq!(after.len(), 4);
+        for (before, after) in before.iter().zip(&after) {
+            assert_eq!(before.key_expr, after.key_expr);
+            assert_eq!(before.kind, after.kind);
+            assert_eq!(before.value.to_string(), after.value.to_string());
+            assert_eq!(before.timestamp, after.timestamp);
+            assert_eq!(before.source_info.source_id, after.source_info.source_id);
+            assert_eq!(before.source_info.source_sn, after.source_info.source_sn);
+        }
+        assert_eq!(after[0].source_info.source_sn, Some(7));
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[cfg(feature = "persistence")]
+#[test]
+fn publication_cache_persistence_compaction() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17478"]).await;
+        let path =
+            std::env::temp_dir().join(format!("zenoh-pubcache-compaction-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        println!(
+            "[PC][01b] Declaring PublicationCache persisted to {:?}",
+            path
+        );
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/compaction")
+            .persistence(&path)
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing many times on the same resource");
+        for i in 0..2049 {
+            ztimeout!(peer01
+                .put("test/pubcache/compaction", format!("{i:04}"))
+                .res_async())
+            .unwrap();
+        }
+        // let the cache receive all the publications
+        for _ in 0..10 {
+            task::sleep(SLEEP).await;
+            if cache.stats().samples_cached == 2049 {
+                break;
+            }
+        }
+        assert_eq!(cache.stats().samples_cached, 2049);
+
+        println!("[PC][03b] Checking that the persistence file was compacted");
+        let len = std::fs::metadata(&path).unwrap().len();
+        assert!(len > 0 && len < 1024, "file of {len} bytes");
+        let replies = get(&peer02, "test/pubcache/compaction").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].value.to_string(), "2048");
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_time_range_replay() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17492"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache on peer01 session");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/time/**")
+            .history(4)
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing an old and a recent sample");
+        ztimeout!(peer01.put("test/pubcache/time/a", "old").res_async()).unwrap();
+        task::sleep(SLEEP * 2).await;
+        ztimeout!(peer01.put("test/pubcache/time/a", "recent").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+
+        println!("[PC][03b] Querying the publications by time range");
+        let values = |samples: Vec<Sample>| -> Vec<String> {
+            samples
+                .iter()
+                .map(|sample| sample.value.to_string())
+                .collect()
+        };
+        assert_eq!(
+            values(get(&peer02, "test/pubcache/time/a").await),
+            ["old", "recent"]
+        );
+        assert_eq!(
+            values(get(&peer02, "test/pubcache/time/a?_time=[now(-2s)..]").await),
+            ["recent"]
+        );
+        assert_eq!(
+            values(get(&peer02, "test/pubcache/time/a?_time=[..now(-2s)]").await),
+            ["old"]
+        );
+        // an invalid time range is ignored
+        assert_eq!(
+            values(get(&peer02, "test/pubcache/time/a?_time=invalid").await),
+            ["old", "recent"]
+        );
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_filter() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17493"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache skipping the heartbeats");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/filter/**")
+            .history(4)
+            .filter(|sample| !sample.key_expr.as_str().ends_with("/heartbeat"))
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing data and heartbeats");
+        for i in 0..2 {
+            ztimeout!(peer01
+                .put("test/pubcache/filter/data", i.to_string())
+                .res_async())
+            .unwrap();
+            ztimeout!(peer01
+                .put("test/pubcache/filter/heartbeat", i.to_string())
+                .res_async())
+            .unwrap();
+        }
+        task::sleep(SLEEP).await;
+
+        println!("[PC][03b] Checking that only the data is cached");
+        let replies = get(&peer02, "test/pubcache/filter/**").await;
+        assert_eq!(replies.len(), 2);
+        assert!(replies
+            .iter()
+            .all(|reply| reply.key_expr.as_str() == "test/pubcache/filter/data"));
+        assert_eq!(cache.stats().samples_cached, 2);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_delete_evicts() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17494"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache evicting the puts on delete");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/delete/**")
+            .history(4)
+            .delete_evicts(true)
+            .res_async())
+        .unwrap();
+
+        println!("[PC][02b] Publishing puts then a delete");
+        for i in 0..2 {
+            ztimeout!(peer01
+                .put("test/pubcache/delete/a", i.to_string())
+                .res_async())
+            .unwrap();
+        }
+        ztimeout!(peer01.put("test/pubcache/delete/b", "b").res_async()).unwrap();
+        ztimeout!(peer01.delete("test/pubcache/delete/a").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+
+        println!("[PC][03b] Checking that only the delete remains for the deleted key");
+        let replies = get(&peer02, "test/pubcache/delete/a").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].kind, SampleKind::Delete);
+        assert_eq!(get(&peer02, "test/pubcache/delete/b").await.len(), 1);
+        assert_eq!(cache.stats().evicted_deleted, 2);
+
+        println!("[PC][04b] Publishing again on the deleted key");
+        ztimeout!(peer01.put("test/pubcache/delete/a", "again").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        let kinds: Vec<SampleKind> = get(&peer02, "test/pubcache/delete/a")
+            .await
+            .iter()
+            .map(|sample| sample.kind)
+            .collect();
+        assert_eq!(kinds, [SampleKind::Delete, SampleKind::Put]);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_pause_resume() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17495"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache on peer01 session");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/pause/**")
+            .history(4)
+            .res_async())
+        .unwrap();
+        ztimeout!(peer01.put("test/pubcache/pause/a", "before").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+
+        println!("[PC][02b] Publishing while the PublicationCache is paused");
+        ztimeout!(cache.pause().res_async()).unwrap();
+        ztimeout!(peer01.put("test/pubcache/pause/a", "paused").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        // the cached publications are still replied while paused
+        let replies = get(&peer02, "test/pubcache/pause/a").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].value.to_string(), "before");
+
+        println!("[PC][03b] Publishing once the PublicationCache is resumed");
+        ztimeout!(cache.resume().res_async()).unwrap();
+        ztimeout!(peer01.put("test/pubcache/pause/a", "after").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        let values: Vec<String> = get(&peer02, "test/pubcache/pause/a")
+            .await
+            .iter()
+            .map(|sample| sample.value.to_string())
+            .collect();
+        assert_eq!(values, ["before", "after"]);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn get_history_merges_redundant_caches() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17496"]).await;
+
+        println!("[PC][01b] Declaring 2 redundant PublicationCaches on peer01 session");
+        let cache1 = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/history/**")
+            .history(4)
+            .res_async())
+        .unwrap();
+        let cache2 = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/history/**")
+            .history(4)
+            .res_async())
+        .unwrap();
+        for i in 0..3 {
+            for key in ["a", "b"] {
+                ztimeout!(peer01
+                    .put(format!("test/pubcache/history/{key}"), i.to_string())
+                    .res_async())
+                .unwrap();
+            }
+        }
+        task::sleep(SLEEP).await;
+
+        println!("[PC][02b] Getting the history from peer02 session");
+        let samples =
+            ztimeout!(peer02.get_history("test/pubcache/history/**").res_async()).unwrap();
+        assert_eq!(samples.len(), 12);
+        assert!(samples
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+
+        println!("[PC][03b] Getting the consolidated and limited history from peer02 session");
+        let samples = ztimeout!(peer02
+            .get_history("test/pubcache/history/**")
+            .history(2)
+            .consolidate()
+            .res_async())
+        .unwrap();
+        let samples: Vec<String> = samples
+            .iter()
+            .map(|sample| format!("{}={}", sample.key_expr, sample.value))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                "test/pubcache/history/a=1",
+                "test/pubcache/history/b=1",
+                "test/pubcache/history/a=2",
+                "test/pubcache/history/b=2"
+            ]
+        );
+
+        ztimeout!(cache1.close().res_async()).unwrap();
+        ztimeout!(cache2.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn publication_cache_export_restore_state() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17497"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache on peer01 session");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/state/**")
+            .history(3)
+            .res_async())
+        .unwrap();
+        for i in 0..4 {
+            ztimeout!(peer01
+                .put("test/pubcache/state/a", i.to_string())
+                .res_async())
+            .unwrap();
+        }
+        ztimeout!(peer01.delete("test/pubcache/state/b").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        let before = get(&peer02, "test/pubcache/state/**").await;
+        assert_eq!(before.len(), 4);
+
+        println!("[PC][02b] Exporting the state of the PublicationCache");
+        let state = ztimeout!(cache.export_state().res_async()).unwrap();
+        ztimeout!(cache.close().res_async()).unwrap();
+        let bytes = state.to_bytes();
+        assert!(CacheState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let state = CacheState::from_bytes(&bytes).unwrap();
+
+        println!("[PC][03b] Restoring the state in a PublicationCache with a smaller history");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/state/**")
+            .history(2)
+            .restore_state(state)
+            .res_async())
+        .unwrap();
+        task::sleep(SLEEP).await;
+        let after = get(&peer02, "test/pubcache/state/**").await;
+        let mut after: Vec<(String, SampleKind, String)> = after
+            .iter()
+            .map(|sample| {
+                (
+                    sample.key_expr.to_string(),
+                    sample.kind,
+                    sample.value.to_string(),
+                )
+            })
+            .collect();
+        // the resources are replied in no particular order
+        after.sort_by(|x, y| (&x.0, &x.2).cmp(&(&y.0, &y.2)));
+        assert_eq!(
+            after,
+            [
+                ("test/pubcache/state/a".into(), SampleKind::Put, "2".into()),
+                ("test/pubcache/state/a".into(), SampleKind::Put, "3".into()),
+                (
+                    "test/pubcache/state/b".into(),
+                    SampleKind::Delete,
+                    "".into()
+                ),
+            ]
+        );
+        // the dropped publication is the oldest one on a
+        let mut timestamps: Vec<_> = before.iter().map(|sample| sample.timestamp).collect();
+        timestamps.sort();
+        timestamps.remove(0);
+        let mut restored: Vec<_> = get(&peer02, "test/pubcache/state/**")
+            .await
+            .iter()
+            .map(|sample| sample.timestamp)
+            .collect();
+        restored.sort();
+        assert_eq!(restored, timestamps);
+
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[cfg(feature = "bundle")]
+#[test]
+fn publication_cache_bundle_round_trip() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17498"]).await;
+
+        println!("[PC][01b] Declaring PublicationCache on peer01 session");
+        let cache = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/bundle/**")
+            .history(4)
+            .res_async())
+        .unwrap();
+        for key in ["a", "b", "c"] {
+            ztimeout!(peer01
+                .put(format!("test/pubcache/bundle/{key}"), key)
+                .res_async())
+            .unwrap();
+        }
+        ztimeout!(peer01.delete("test/pubcache/bundle/a").res_async()).unwrap();
+        task::sleep(SLEEP).await;
+
+        println!("[PC][02b] Querying a bundle from peer02 session");
+        let replies = get(&peer02, "test/pubcache/bundle/**?_encoding=bundle").await;
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].value.encoding.to_string(), BUNDLE_ENCODING);
+        let mut unbundled = unbundle(&replies[0]).unwrap();
+        let mut expected = get(&peer02, "test/pubcache/bundle/**").await;
+        assert_eq!(unbundled.len(), 4);
+        unbundled.sort_by_key(|sample| sample.timestamp);
+        expected.sort_by_key(|sample| sample.timestamp);
+        for (unbundled, expected) in unbundled.iter().zip(&expected) {
+            assert_eq!(unbundled.key_expr, expected.key_expr);
+            assert_eq!(unbundled.kind, expected.kind);
+            assert_eq!(unbundled.value.to_string(), expected.value.to_string());
+            assert_eq!(unbundled.timestamp, expected.timestamp);
+        }
+        assert!(unbundle(&expected[0]).is_err());
+
+        println!("[PC][03b] Declaring a QueryingSubscriber querying a bundle on peer02 session");
+        let subscriber = ztimeout!(peer02
+            .declare_subscriber("test/pubcache/bundle/**")
+            .querying()
+            .query_bundle(true)
+            .res_async())
+        .unwrap();
+        task::sleep(SLEEP).await;
+        let mut received = Vec::new();
+        while let Ok(sample) = subscriber.try_recv() {
+            received.push(sample.timestamp);
+        }
+        let expected: Vec<_> = expected.iter().map(|sample| sample.timestamp).collect();
+        assert_eq!(received, expected);
+
+        ztimeout!(subscriber.close().res_async()).unwrap();
+        ztimeout!(cache.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn list_caches_discovers_the_admin_caches() {
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17499"]).await;
+
+        println!("[PC][01b] Declaring PublicationCaches with and without admin");
+        let admin = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/admin/a/**")
+            .history(3)
+            .queryable_prefix("test/prefix")
+            .admin(true)
+            .res_async())
+        .unwrap();
+        let hidden = ztimeout!(peer01
+            .declare_publication_cache("test/pubcache/admin/b/**")
+            .res_async())
+        .unwrap();
+        for key in ["x", "y"] {
+            ztimeout!(peer01
+                .put(format!("test/pubcache/admin/a/{key}"), key)
+                .res_async())
+            .unwrap();
+        }
+        task::sleep(SLEEP).await;
+
+        println!("[PC][02b] Listing the PublicationCaches from peer02 session");
+        let caches = ztimeout!(list_caches(&peer02).res_async()).unwrap();
+        assert_eq!(caches.len(), 1);
+        assert_eq!(caches[0].zid, peer01.zid());
+        assert_eq!(caches[0].pub_key_expr.as_str(), "test/pubcache/admin/a/**");
+        assert_eq!(
+            caches[0].queryable_key_exprs[0].as_str(),
+            "test/prefix/test/pubcache/admin/a/**"
+        );
+        assert_eq!(caches[0].history, Some(3));
+        assert_eq!(caches[0].resources, 2);
+        assert_eq!(caches[0].stats.samples_cached, 2);
+
+        println!("[PC][03b] Listing the PublicationCaches once the admin one is closed");
+        ztimeout!(admin.close().res_async()).unwrap();
+        task::sleep(SLEEP).await;
+        assert!(ztimeout!(list_caches(&peer02).res_async())
+            .unwrap()
+            .is_empty());
+
+        ztimeout!(hidden.close().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
+
+#[test]
+fn querying_subscriber_detects_gaps() {
+    use std::sync::{Arc, Mutex};
+    task::block_on(async {
+        let (peer01, peer02) = open_session(&["tcp/127.0.0.1:17500"]).await;
+
+        println!("[PC][01b] Declaring a queryable replying slowly with a history sample");
+        let history_ts = peer01.hlc().unwrap().new_timestamp();
+        let reply_ts = Arc::new(Mutex::new(history_ts));
+        let queryable = ztimeout!(peer01
+            .declare_queryable("test/pubcache/gap/**")
+            .callback({
+                let reply_ts = reply_ts.clone();
+                move |query| {
+                    let reply_ts = reply_ts.clone();
+                    task::spawn(async move {
+                        // let the live publication be received first
+                        task::sleep(SLEEP).await;
+                        let timestamp = *reply_ts.lock().unwrap();
+                        let sample = Sample::new(
+                            KeyExpr::try_from("test/pubcache/gap/a").unwrap(),
+                            "history",
+                        )
+                        .with_timestamp(timestamp);
+                        query.reply(Ok(sample)).res_async().await.unwrap();
+                    });
+                }
+            })
+            .res_async())
+        .unwrap();
+        // let the queryable be declared on peer02 session
+        task::sleep(SLEEP).await;
+
+        for overlapping in [false, true] {
+            println!("[PC][02b] Declaring a QueryingSubscriber (overlapping={overlapping})");
+            let gaps = Arc::new(Mutex::new(Vec::new()));
+            let subscriber = ztimeout!(peer02
+                .declare_subscriber("test/pubcache/gap/**")
+                .querying()
+                .on_gap({
+                    let gaps = gaps.clone();
+                    move |gap| gaps.lock().unwrap().push(gap)
+                })
+                .res_async())
+            .unwrap();
+            task::sleep(SLEEP / 4).await;
+            ztimeout!(peer01.put("test/pubcache/gap/a", "live").res_async()).unwrap();
+            if overlapping {
+                // the history then extends past the live publication
+                *reply_ts.lock().unwrap() = peer01.hlc().unwrap().new_timestamp();
+            }
+            task::sleep(SLEEP * 2).await;
+
+            let mut received = 0;
+            while subscriber.try_recv().is_ok() {
+                received += 1;
+            }
+            assert_eq!(received, 2);
+            let gaps = std::mem::take(&mut *gaps.lock().unwrap());
+            if overlapping {
+                assert!(gaps.is_empty(), "unexpected gaps: {:?}", gaps);
+            } else {
+                assert_eq!(gaps.len(), 1);
+                assert_eq!(gaps[0].key_expr.as_str(), "test/pubcache/gap/a");
+                assert_eq!(gaps[0].last_history_ts, history_ts);
+            }
+            ztimeout!(subscriber.close().res_async()).unwrap();
+        }
+
+        ztimeout!(queryable.undeclare().res_async()).unwrap();
+        close_session(peer01, peer02).await;
+    });
+}
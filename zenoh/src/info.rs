@@ -13,12 +13,21 @@
 //
 
 //! Tools to access information about the current zenoh [`Session`](crate::Session).
+use crate::net::routing::pubsub::remote_subscribers;
+use crate::net::routing::queries::remote_queryables;
+use crate::prelude::{KeyExpr, Locality, OwnedKeyExpr};
+use crate::time::Timestamp;
 use crate::SessionRef;
+use futures::future::BoxFuture;
+use futures::Stream;
+use std::collections::HashSet;
 use std::future::Ready;
+use std::time::{Duration, Instant};
 use zenoh_config::{WhatAmI, ZenohId};
-use zenoh_core::{AsyncResolve, Resolvable, SyncResolve};
+use zenoh_core::{zread, AsyncResolve, Resolvable, SyncResolve};
+use zenoh_result::{bail, ZResult};
 
-/// A builder retuned by [`SessionInfo::zid()`](SessionInfo::zid) that allows
+/// A builder returned by [`SessionInfo::zid()`](SessionInfo::zid) that allows
 /// to access the [`ZenohId`] of the current zenoh [`Session`](crate::Session).
 ///
 /// # Examples
@@ -52,6 +61,191 @@ impl<'a> AsyncResolve for ZidBuilder<'a> {
     }
 }
 
+/// A builder returned by [`SessionInfo::now()`](SessionInfo::now) that allows
+/// to read the current time of the HLC of the current zenoh [`Session`](crate::Session).
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let now = session.info().now().res().await;
+/// # })
+/// ```
+pub struct NowBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for NowBuilder<'a> {
+    type To = Option<Timestamp>;
+}
+
+impl<'a> SyncResolve for NowBuilder<'a> {
+    fn res_sync(self) -> Self::To {
+        self.session.hlc().map(|hlc| hlc.new_timestamp())
+    }
+}
+
+impl<'a> AsyncResolve for NowBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::whatami()`](SessionInfo::whatami) that allows
+/// to access the [`WhatAmI`] of the current zenoh [`Session`](crate::Session).
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let whatami = session.info().whatami().res().await;
+/// # })
+/// ```
+pub struct WhatAmIBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for WhatAmIBuilder<'a> {
+    type To = WhatAmI;
+}
+
+impl<'a> SyncResolve for WhatAmIBuilder<'a> {
+    fn res_sync(self) -> Self::To {
+        self.session.runtime.whatami
+    }
+}
+
+impl<'a> AsyncResolve for WhatAmIBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::timestamping_enabled()`](SessionInfo::timestamping_enabled)
+/// that allows to know whether timestamping is enabled for the current zenoh [`Session`](crate::Session).
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let enabled = session.info().timestamping_enabled().res().await;
+/// # })
+/// ```
+pub struct TimestampingEnabledBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for TimestampingEnabledBuilder<'a> {
+    type To = bool;
+}
+
+impl<'a> SyncResolve for TimestampingEnabledBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        self.session.hlc().is_some()
+    }
+}
+
+impl<'a> AsyncResolve for TimestampingEnabledBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::listen_locators()`](SessionInfo::listen_locators) that allows
+/// to access the locators the current zenoh [`Session`](crate::Session) is listening on.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let locators = session.info().listen_locators().res().await;
+/// # })
+/// ```
+pub struct ListenLocatorsBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for ListenLocatorsBuilder<'a> {
+    type To = Vec<String>;
+}
+
+impl<'a> SyncResolve for ListenLocatorsBuilder<'a> {
+    fn res_sync(self) -> Self::To {
+        self.session
+            .runtime
+            .get_locators()
+            .iter()
+            .map(|l| l.to_string())
+            .collect()
+    }
+}
+
+impl<'a> AsyncResolve for ListenLocatorsBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::connect_locators()`](SessionInfo::connect_locators) that allows
+/// to access the locators of the zenoh nodes the current zenoh [`Session`](crate::Session) is
+/// currently connected to, i.e. the destination locators of the links of its transports
+/// (see [`LinkInfo::dst`]).
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let locators = session.info().connect_locators().res().await;
+/// # })
+/// ```
+pub struct ConnectLocatorsBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for ConnectLocatorsBuilder<'a> {
+    type To = Vec<String>;
+}
+
+impl<'a> SyncResolve for ConnectLocatorsBuilder<'a> {
+    fn res_sync(self) -> Self::To {
+        self.session
+            .runtime
+            .manager()
+            .get_transports()
+            .into_iter()
+            .filter_map(|s| s.get_links().ok())
+            .flatten()
+            .map(|l| l.dst.to_string())
+            .collect()
+    }
+}
+
+impl<'a> AsyncResolve for ConnectLocatorsBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
 /// A builder returned by [`SessionInfo::routers_zid()`](SessionInfo::routers_zid) that allows
 /// to access the [`ZenohId`] of the zenoh routers this process is currently connected to
 /// or the [`ZenohId`] of the current router if this code is run from a router (plugin).
@@ -100,7 +294,7 @@ impl<'a> AsyncResolve for RoutersZidBuilder<'a> {
     }
 }
 
-/// A builder retuned by [`SessionInfo::peers_zid()`](SessionInfo::peers_zid) that allows
+/// A builder returned by [`SessionInfo::peers_zid()`](SessionInfo::peers_zid) that allows
 /// to access the [`ZenohId`] of the zenoh peers this process is currently connected to.
 ///
 /// # Examples
@@ -148,75 +342,1168 @@ impl<'a> AsyncResolve for PeersZidBuilder<'a> {
     }
 }
 
-/// Struct returned by [`Session::info()`](crate::Session::info) which allows
-/// to access informations about the current zenoh [`Session`](crate::Session).
+/// A builder returned by [`SessionInfo::routers_zid_stream()`](SessionInfo::routers_zid_stream) and
+/// [`SessionInfo::peers_zid_stream()`](SessionInfo::peers_zid_stream) that allows to access
+/// the [`ZenohId`] of the zenoh routers or peers this process is currently connected to.
+///
+/// Nothing is collected when resolving this builder: each time the resulting [`Stream`] is polled,
+/// it looks up the transport of the next connected zenoh node in [`ZenohId`] order, until one of
+/// the requested [`WhatAmI`]. So the nodes connecting in the meantime are only yielded if their
+/// [`ZenohId`] is greater than the last yielded one, and the nodes disconnecting before being
+/// reached are skipped.
 ///
 /// # Examples
 /// ```
 /// # async_std::task::block_on(async {
+/// use futures::StreamExt;
 /// use zenoh::prelude::r#async::*;
 ///
 /// let session = zenoh::open(config::peer()).res().await.unwrap();
-/// let info = session.info();
-/// let zid = info.zid().res().await;
+/// let mut peers_zid = session.info().peers_zid_stream().res().await;
+/// while let Some(peer_zid) = peers_zid.next().await {}
 /// # })
 /// ```
-pub struct SessionInfo<'a> {
+pub struct ZidStreamBuilder<'a> {
     pub(crate) session: SessionRef<'a>,
+    pub(crate) whatami: WhatAmI,
 }
 
-impl SessionInfo<'_> {
-    /// Return the [`ZenohId`] of the current zenoh [`Session`](crate::Session).
-    ///
-    /// # Examples
-    /// ```
-    /// # async_std::task::block_on(async {
-    /// use zenoh::prelude::r#async::*;
-    ///
-    /// let session = zenoh::open(config::peer()).res().await.unwrap();
-    /// let zid = session.info().zid().res().await;
-    /// # })
-    /// ```
-    pub fn zid(&self) -> ZidBuilder<'_> {
-        ZidBuilder {
-            session: self.session.clone(),
-        }
+impl<'a> Resolvable for ZidStreamBuilder<'a> {
+    type To = Box<dyn Stream<Item = ZenohId> + Send + Sync + Unpin>;
+}
+
+impl<'a> SyncResolve for ZidStreamBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        let manager = self.session.runtime.manager().clone();
+        let whatami = self.whatami;
+        let mut last = None;
+        let zids = std::iter::from_fn(move || loop {
+            let (zid, transport) = manager.get_transport_after(last.as_ref())?;
+            last = Some(zid);
+            if transport
+                .get_whatami()
+                .map_or(false, |what| what == whatami)
+            {
+                return Some(zid);
+            }
+        });
+        Box::new(futures::stream::iter(zids))
     }
+}
 
-    /// Return the [`ZenohId`] of the zenoh routers this process is currently connected to
-    /// or the [`ZenohId`] of the current router if this code is run from a router (plugin).
-    ///
-    /// # Examples
-    /// ```
-    /// # async_std::task::block_on(async {
-    /// use zenoh::prelude::r#async::*;
-    ///
-    /// let session = zenoh::open(config::peer()).res().await.unwrap();
-    /// let mut routers_zid = session.info().routers_zid().res().await;
-    /// while let Some(router_zid) = routers_zid.next() {}
-    /// # })
-    /// ```
-    pub fn routers_zid(&self) -> RoutersZidBuilder<'_> {
-        RoutersZidBuilder {
-            session: self.session.clone(),
+impl<'a> AsyncResolve for ZidStreamBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::whatami_of()`](SessionInfo::whatami_of) that allows
+/// to access the [`WhatAmI`] of a zenoh node this process is currently connected to.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let zid = session.info().zid().res().await;
+/// let whatami = session.info().whatami_of(zid).res().await;
+/// # })
+/// ```
+pub struct WhatAmIOfBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+    pub(crate) zid: ZenohId,
+}
+
+impl<'a> Resolvable for WhatAmIOfBuilder<'a> {
+    type To = Option<WhatAmI>;
+}
+
+impl<'a> SyncResolve for WhatAmIOfBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        self.session
+            .runtime
+            .manager()
+            .get_transports()
+            .into_iter()
+            .find(|s| s.get_zid().map_or(false, |zid| zid == self.zid))
+            .and_then(|s| s.get_whatami().ok())
+    }
+}
+
+impl<'a> AsyncResolve for WhatAmIOfBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// An event notifying that a zenoh node connected to or disconnected from the current zenoh
+/// [`Session`](crate::Session).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerEvent {
+    /// A transport with the zenoh node of the given [`ZenohId`] has been established.
+    Joined(ZenohId),
+    /// The transport with the zenoh node of the given [`ZenohId`] has been closed.
+    Left(ZenohId),
+}
+
+/// A receiver of [`PeerEvent`]s, returned by [`SessionInfo::peers_changes()`](SessionInfo::peers_changes)
+/// and [`SessionInfo::routers_changes()`](SessionInfo::routers_changes).
+///
+/// The events stop being produced once this receiver is dropped.
+#[derive(Debug)]
+pub struct PeerEvents {
+    receiver: flume::Receiver<PeerEvent>,
+}
+
+impl std::ops::Deref for PeerEvents {
+    type Target = flume::Receiver<PeerEvent>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+/// A builder returned by [`SessionInfo::peers_changes()`](SessionInfo::peers_changes) and
+/// [`SessionInfo::routers_changes()`](SessionInfo::routers_changes) that allows to be notified
+/// when zenoh peers or routers connect to or disconnect from the current zenoh [`Session`](crate::Session).
+///
+/// # Examples
+/// ```no_run
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+/// use zenoh::info::PeerEvent;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let events = session.info().peers_changes().res().await;
+/// while let Ok(event) = events.recv_async().await {
+///     match event {
+///         PeerEvent::Joined(zid) => println!("{} joined", zid),
+///         PeerEvent::Left(zid) => println!("{} left", zid),
+///     }
+/// }
+/// # })
+/// ```
+pub struct PeersChangesBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+    pub(crate) whatami: WhatAmI,
+}
+
+impl<'a> Resolvable for PeersChangesBuilder<'a> {
+    type To = PeerEvents;
+}
+
+impl<'a> SyncResolve for PeersChangesBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        let (sender, receiver) = flume::unbounded();
+        self.session
+            .runtime
+            .new_peer_events_listener(self.whatami, sender);
+        PeerEvents { receiver }
+    }
+}
+
+impl<'a> AsyncResolve for PeersChangesBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::wait_for_peers()`](SessionInfo::wait_for_peers) that allows
+/// to wait until this process is connected to a number of zenoh peers.
+///
+/// # Examples
+/// ```no_run
+/// # async_std::task::block_on(async {
+/// use std::time::Duration;
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// session.info().wait_for_peers(2, Duration::from_secs(10)).res().await.unwrap();
+/// # })
+/// ```
+pub struct WaitForPeersBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+    pub(crate) n: usize,
+    pub(crate) timeout: Duration,
+}
+
+impl<'a> WaitForPeersBuilder<'a> {
+    async fn wait(self) -> ZResult<()> {
+        let info = SessionInfo {
+            session: self.session,
+        };
+        // a timeout too long to be represented never expires
+        let deadline = Instant::now().checked_add(self.timeout);
+        // listen before listing the connected peers, to miss no event in between
+        let events = info.peers_changes().res_sync();
+        let mut peers: HashSet<ZenohId> = info.peers_zid().res_sync().collect();
+        while peers.len() < self.n {
+            let event = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    async_std::future::timeout(remaining, events.recv_async()).await
+                }
+                None => Ok(events.recv_async().await),
+            };
+            match event {
+                Ok(Ok(PeerEvent::Joined(zid))) => {
+                    peers.insert(zid);
+                }
+                Ok(Ok(PeerEvent::Left(zid))) => {
+                    peers.remove(&zid);
+                }
+                Ok(Err(_)) => bail!("Session closed while waiting for {} peers", self.n),
+                Err(_) => bail!(
+                    "Timeout while waiting for {} peers: only {} connected after {:?}",
+                    self.n,
+                    peers.len(),
+                    self.timeout
+                ),
+            }
         }
+        Ok(())
     }
+}
 
-    /// Return the [`ZenohId`] of the zenoh peers this process is currently connected to.
-    ///
-    /// # Examples
-    /// ```
-    /// # async_std::task::block_on(async {
-    /// use zenoh::prelude::r#async::*;
-    ///
-    /// let session = zenoh::open(config::peer()).res().await.unwrap();
-    /// let mut peers_zid = session.info().peers_zid().res().await;
-    /// while let Some(peer_zid) = peers_zid.next() {}
-    /// # })
+impl<'a> Resolvable for WaitForPeersBuilder<'a> {
+    type To = ZResult<()>;
+}
+
+impl<'a> SyncResolve for WaitForPeersBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        async_std::task::block_on(self.wait())
+    }
+}
+
+impl<'a> AsyncResolve for WaitForPeersBuilder<'a> {
+    type Future = BoxFuture<'a, Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        Box::pin(self.wait())
+    }
+}
+
+/// Informations on a link of a transport the current zenoh [`Session`](crate::Session) has with another zenoh node.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkInfo {
+    /// The [`ZenohId`] of the remote zenoh node.
+    pub zid: ZenohId,
+    /// The [`WhatAmI`] of the remote zenoh node.
+    pub whatami: WhatAmI,
+    /// The source locator of the link.
+    pub src: String,
+    /// The destination locator of the link.
+    pub dst: String,
+    /// Whether the link is reliable.
+    pub is_reliable: bool,
+}
+
+/// A builder returned by [`SessionInfo::links()`](SessionInfo::links) that allows
+/// to access the [`LinkInfo`] of all the links of the transports the current zenoh
+/// [`Session`](crate::Session) has with other zenoh nodes.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let links = session.info().links().res().await;
+/// for link in links {
+///     println!("{} ({}): {} => {}", link.zid, link.whatami, link.src, link.dst);
+/// }
+/// # })
+/// ```
+pub struct LinksBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for LinksBuilder<'a> {
+    type To = Vec<LinkInfo>;
+}
+
+impl<'a> SyncResolve for LinksBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        self.session
+            .runtime
+            .manager()
+            .get_transports()
+            .into_iter()
+            .filter_map(|s| {
+                let zid = s.get_zid().ok()?;
+                let whatami = s.get_whatami().ok()?;
+                let links = s.get_links().ok()?;
+                Some(links.into_iter().map(move |l| LinkInfo {
+                    zid,
+                    whatami,
+                    src: l.src.to_string(),
+                    dst: l.dst.to_string(),
+                    is_reliable: l.is_reliable,
+                }))
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+impl<'a> AsyncResolve for LinksBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// Details on a zenoh node the current zenoh [`Session`](crate::Session) is connected to.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerDetail {
+    /// The [`ZenohId`] of the remote zenoh node.
+    pub zid: ZenohId,
+    /// The [`WhatAmI`] of the remote zenoh node.
+    pub whatami: WhatAmI,
+    /// The time at which the transport with the remote zenoh node has been established.
+    pub established: Instant,
+    /// How long the transport with the remote zenoh node has been established,
+    /// when this [`PeerDetail`] was collected.
+    pub uptime: Duration,
+}
+
+/// A builder returned by [`SessionInfo::peers_detailed()`](SessionInfo::peers_detailed) that allows
+/// to access the [`PeerDetail`] of all the zenoh nodes the current zenoh [`Session`](crate::Session)
+/// is connected to.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let peers = session.info().peers_detailed().res().await;
+/// for peer in peers {
+///     println!("{} ({}): up for {:?}", peer.zid, peer.whatami, peer.uptime);
+/// }
+/// # })
+/// ```
+pub struct PeersDetailedBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for PeersDetailedBuilder<'a> {
+    type To = Vec<PeerDetail>;
+}
+
+impl<'a> SyncResolve for PeersDetailedBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        let now = Instant::now();
+        self.session
+            .runtime
+            .manager()
+            .get_transports()
+            .into_iter()
+            .filter_map(|s| {
+                let established = s.get_established().ok()?;
+                Some(PeerDetail {
+                    zid: s.get_zid().ok()?,
+                    whatami: s.get_whatami().ok()?,
+                    established,
+                    uptime: now.saturating_duration_since(established),
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'a> AsyncResolve for PeersDetailedBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::peer_protocols()`](SessionInfo::peer_protocols) that allows
+/// to access the protocol (i.e. the locator scheme, e.g. `tcp`, `tls` or `quic`) of all the links
+/// of the transports the current zenoh [`Session`](crate::Session) has with other zenoh nodes.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// for (zid, protocol) in session.info().peer_protocols().res().await {
+///     println!("{}: {}", zid, protocol);
+/// }
+/// # })
+/// ```
+pub struct PeerProtocolsBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for PeerProtocolsBuilder<'a> {
+    type To = Vec<(ZenohId, String)>;
+}
+
+impl<'a> SyncResolve for PeerProtocolsBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        self.session
+            .runtime
+            .manager()
+            .get_transports()
+            .into_iter()
+            .filter_map(|s| {
+                let zid = s.get_zid().ok()?;
+                let links = s.get_links().ok()?;
+                Some(
+                    links
+                        .into_iter()
+                        .map(move |l| (zid, l.dst.protocol().as_str().to_string())),
+                )
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+impl<'a> AsyncResolve for PeerProtocolsBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// The declarations of the current zenoh [`Session`](crate::Session).
+///
+/// Subscribers and queryables are listed with the id the session assigned them at declaration.
+/// Publishers have no such id: each declared publication key expression is listed once,
+/// whatever the number of [`Publisher`](crate::publication::Publisher)s declared on it.
+///
+/// The queryable the session declares on its own admin space is listed as well.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Declarations {
+    /// The ids and key expressions of the declared subscribers, sorted by id.
+    pub subscribers: Vec<(usize, OwnedKeyExpr)>,
+    /// The ids and key expressions of the declared queryables, sorted by id.
+    pub queryables: Vec<(usize, OwnedKeyExpr)>,
+    /// The key expressions of the declared publishers, in declaration order.
+    pub publishers: Vec<OwnedKeyExpr>,
+}
+
+impl Declarations {
+    /// Whether the session has no declared subscriber, queryable nor publisher.
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty() && self.queryables.is_empty() && self.publishers.is_empty()
+    }
+}
+
+/// A builder returned by [`SessionInfo::declarations()`](SessionInfo::declarations) that allows
+/// to access the [`Declarations`] of the current zenoh [`Session`](crate::Session).
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let subscriber = session.declare_subscriber("key/expression").res().await.unwrap();
+/// let declarations = session.info().declarations().res().await;
+/// assert_eq!(declarations.subscribers.len(), 1);
+/// # })
+/// ```
+pub struct DeclarationsBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl<'a> Resolvable for DeclarationsBuilder<'a> {
+    type To = Declarations;
+}
+
+impl<'a> SyncResolve for DeclarationsBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        let state = zread!(self.session.state);
+        let mut subscribers: Vec<(usize, OwnedKeyExpr)> = state
+            .subscribers
+            .values()
+            .map(|s| (s.id, (*s.key_expr).to_owned()))
+            .collect();
+        subscribers.sort_by_key(|(id, _)| *id);
+        let mut queryables: Vec<(usize, OwnedKeyExpr)> = state
+            .queryables
+            .values()
+            .filter_map(|q| match state.local_wireexpr_to_expr(&q.key_expr) {
+                Ok(key_expr) => Some((q.id, (*key_expr).to_owned())),
+                Err(e) => {
+                    log::error!(
+                        "Unable to resolve key expression of queryable {}: {}",
+                        q.id,
+                        e
+                    );
+                    None
+                }
+            })
+            .collect();
+        queryables.sort_by_key(|(id, _)| *id);
+        Declarations {
+            subscribers,
+            queryables,
+            publishers: state.publications.clone(),
+        }
+    }
+}
+
+impl<'a> AsyncResolve for DeclarationsBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::matching_subscribers()`](SessionInfo::matching_subscribers)
+/// that allows to count the subscribers matching a key expression.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let subscriber = session.declare_subscriber("key/expression").res().await.unwrap();
+/// let count = session.info().matching_subscribers("key/**").res().await.unwrap();
+/// assert_eq!(count, 1);
+/// # })
+/// ```
+pub struct MatchingSubscribersBuilder<'a, 'b> {
+    pub(crate) session: SessionRef<'a>,
+    pub(crate) key_expr: ZResult<KeyExpr<'b>>,
+}
+
+impl<'a, 'b> Resolvable for MatchingSubscribersBuilder<'a, 'b> {
+    type To = ZResult<usize>;
+}
+
+impl<'a, 'b> SyncResolve for MatchingSubscribersBuilder<'a, 'b> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        let key_expr = self.key_expr?;
+        let local = zread!(self.session.state)
+            .subscribers
+            .values()
+            .filter(|s| s.origin != Locality::Remote && s.key_expr.intersects(&key_expr))
+            .count();
+        let tables = zread!(self.session.runtime.router.tables.tables);
+        let remote = remote_subscribers(&tables, &key_expr).len();
+        Ok(local + remote)
+    }
+}
+
+impl<'a, 'b> AsyncResolve for MatchingSubscribersBuilder<'a, 'b> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// A builder returned by [`SessionInfo::peers_interested_in()`](SessionInfo::peers_interested_in)
+/// that allows to access the [`ZenohId`] of the zenoh nodes that declared subscribers or
+/// queryables matching a key expression.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let mut peers_zid = session.info().peers_interested_in("key/**").res().await.unwrap();
+/// while let Some(peer_zid) = peers_zid.next() {}
+/// # })
+/// ```
+pub struct PeersInterestedInBuilder<'a, 'b> {
+    pub(crate) session: SessionRef<'a>,
+    pub(crate) key_expr: ZResult<KeyExpr<'b>>,
+}
+
+impl<'a, 'b> Resolvable for PeersInterestedInBuilder<'a, 'b> {
+    type To = ZResult<Box<dyn Iterator<Item = ZenohId> + Send + Sync>>;
+}
+
+impl<'a, 'b> SyncResolve for PeersInterestedInBuilder<'a, 'b> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        let key_expr = self.key_expr?;
+        let tables = zread!(self.session.runtime.router.tables.tables);
+        let mut zids: Vec<ZenohId> = remote_subscribers(&tables, &key_expr)
+            .union(&remote_queryables(&tables, &key_expr))
+            .copied()
+            .collect();
+        zids.sort_unstable();
+        Ok(Box::new(zids.into_iter()))
+    }
+}
+
+impl<'a, 'b> AsyncResolve for PeersInterestedInBuilder<'a, 'b> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// Statistics of the transport the current zenoh [`Session`](crate::Session) has with another zenoh node.
+///
+/// The counters are cumulative since the establishment of the transport and account for all its links.
+/// Only available with the `stats` feature.
+#[cfg(feature = "stats")]
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkStats {
+    /// The [`ZenohId`] of the remote zenoh node.
+    pub zid: ZenohId,
+    /// The number of bytes sent to the remote zenoh node.
+    pub bytes_sent: u64,
+    /// The number of bytes received from the remote zenoh node.
+    pub bytes_received: u64,
+    /// The number of zenoh messages sent to the remote zenoh node.
+    pub messages_sent: u64,
+    /// The number of zenoh messages received from the remote zenoh node.
+    pub messages_received: u64,
+}
+
+/// A builder returned by [`SessionInfo::link_stats()`](SessionInfo::link_stats) that allows
+/// to access the [`LinkStats`] of all the transports the current zenoh
+/// [`Session`](crate::Session) has with other zenoh nodes. Only available with the `stats` feature.
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let stats = session.info().link_stats().res().await;
+/// for s in stats {
+///     println!("{}: sent {} bytes, received {} bytes", s.zid, s.bytes_sent, s.bytes_received);
+/// }
+/// # })
+/// ```
+#[cfg(feature = "stats")]
+pub struct LinkStatsBuilder<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+#[cfg(feature = "stats")]
+impl<'a> Resolvable for LinkStatsBuilder<'a> {
+    type To = Vec<LinkStats>;
+}
+
+#[cfg(feature = "stats")]
+impl<'a> SyncResolve for LinkStatsBuilder<'a> {
+    fn res_sync(self) -> <Self as Resolvable>::To {
+        self.session
+            .runtime
+            .manager()
+            .get_transports()
+            .into_iter()
+            .filter_map(|s| {
+                let zid = s.get_zid().ok()?;
+                let stats = s.get_stats().ok()?;
+                Some(LinkStats {
+                    zid,
+                    bytes_sent: stats.tx_bytes as u64,
+                    bytes_received: stats.rx_bytes as u64,
+                    messages_sent: stats.tx_z_msgs as u64,
+                    messages_received: stats.rx_z_msgs as u64,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "stats")]
+impl<'a> AsyncResolve for LinkStatsBuilder<'a> {
+    type Future = Ready<Self::To>;
+
+    fn res_async(self) -> Self::Future {
+        std::future::ready(self.res_sync())
+    }
+}
+
+/// Struct returned by [`Session::info()`](crate::Session::info) which allows
+/// to access informations about the current zenoh [`Session`](crate::Session).
+///
+/// # Examples
+/// ```
+/// # async_std::task::block_on(async {
+/// use zenoh::prelude::r#async::*;
+///
+/// let session = zenoh::open(config::peer()).res().await.unwrap();
+/// let info = session.info();
+/// let zid = info.zid().res().await;
+/// # })
+/// ```
+pub struct SessionInfo<'a> {
+    pub(crate) session: SessionRef<'a>,
+}
+
+impl SessionInfo<'_> {
+    /// Return the [`ZenohId`] of the current zenoh [`Session`](crate::Session).
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let zid = session.info().zid().res().await;
+    /// # })
+    /// ```
+    pub fn zid(&self) -> ZidBuilder<'_> {
+        ZidBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the [`WhatAmI`] of the current zenoh [`Session`](crate::Session).
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let whatami = session.info().whatami().res().await;
+    /// # })
+    /// ```
+    pub fn whatami(&self) -> WhatAmIBuilder<'_> {
+        WhatAmIBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return a new [`Timestamp`] generated by the HLC of the current zenoh [`Session`](crate::Session),
+    /// or `None` if timestamping is not enabled for this session.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let now = session.info().now().res().await;
+    /// # })
+    /// ```
+    pub fn now(&self) -> NowBuilder<'_> {
+        NowBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return whether timestamping is enabled for the current zenoh [`Session`](crate::Session),
+    /// i.e. whether its publications are timestamped.
+    ///
+    /// Timestamping can only be enabled in the configuration the [`Session`](crate::Session)
+    /// is opened with (`timestamping/enabled`), as the timestamps are added by its router.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let mut config = config::peer();
+    /// config
+    ///     .timestamping
+    ///     .set_enabled(Some(zenoh::config::ModeDependentValue::Unique(true)))
+    ///     .unwrap();
+    /// let session = zenoh::open(config).res().await.unwrap();
+    /// assert!(session.info().timestamping_enabled().res().await);
+    /// # })
+    /// ```
+    pub fn timestamping_enabled(&self) -> TimestampingEnabledBuilder<'_> {
+        TimestampingEnabledBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the locators this process is listening on.
+    ///
+    /// Those can be given to other zenoh nodes to connect to this process.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let locators = session.info().listen_locators().res().await;
+    /// # })
+    /// ```
+    pub fn listen_locators(&self) -> ListenLocatorsBuilder<'_> {
+        ListenLocatorsBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the locators of the zenoh nodes this process is currently connected to,
+    /// i.e. the destination locators of its [`links()`](SessionInfo::links).
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let locators = session.info().connect_locators().res().await;
+    /// # })
+    /// ```
+    pub fn connect_locators(&self) -> ConnectLocatorsBuilder<'_> {
+        ConnectLocatorsBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the [`ZenohId`] of the zenoh routers this process is currently connected to
+    /// or the [`ZenohId`] of the current router if this code is run from a router (plugin).
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let mut routers_zid = session.info().routers_zid().res().await;
+    /// while let Some(router_zid) = routers_zid.next() {}
+    /// # })
+    /// ```
+    pub fn routers_zid(&self) -> RoutersZidBuilder<'_> {
+        RoutersZidBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the [`ZenohId`] of the zenoh peers this process is currently connected to.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let mut peers_zid = session.info().peers_zid().res().await;
+    /// while let Some(peer_zid) = peers_zid.next() {}
+    /// # })
     /// ```
     pub fn peers_zid(&self) -> PeersZidBuilder<'_> {
         PeersZidBuilder {
             session: self.session.clone(),
         }
     }
+
+    /// Return a [`Stream`] of the [`ZenohId`] of the zenoh routers this process is currently connected to.
+    ///
+    /// Contrary to [`routers_zid()`](SessionInfo::routers_zid), the connected zenoh nodes are not
+    /// collected: they are looked up one at a time as the [`Stream`] is polled
+    /// (see [`ZidStreamBuilder`]).
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use futures::StreamExt;
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let mut routers_zid = session.info().routers_zid_stream().res().await;
+    /// while let Some(router_zid) = routers_zid.next().await {}
+    /// # })
+    /// ```
+    pub fn routers_zid_stream(&self) -> ZidStreamBuilder<'_> {
+        ZidStreamBuilder {
+            session: self.session.clone(),
+            whatami: WhatAmI::Router,
+        }
+    }
+
+    /// Return a [`Stream`] of the [`ZenohId`] of the zenoh peers this process is currently connected to.
+    ///
+    /// Contrary to [`peers_zid()`](SessionInfo::peers_zid), the connected zenoh nodes are not
+    /// collected: they are looked up one at a time as the [`Stream`] is polled
+    /// (see [`ZidStreamBuilder`]).
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use futures::StreamExt;
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let mut peers_zid = session.info().peers_zid_stream().res().await;
+    /// while let Some(peer_zid) = peers_zid.next().await {}
+    /// # })
+    /// ```
+    pub fn peers_zid_stream(&self) -> ZidStreamBuilder<'_> {
+        ZidStreamBuilder {
+            session: self.session.clone(),
+            whatami: WhatAmI::Peer,
+        }
+    }
+
+    /// Return a receiver of the [`PeerEvent`]s notifying when zenoh peers connect to
+    /// or disconnect from this process.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let events = session.info().peers_changes().res().await;
+    /// while let Ok(event) = events.recv_async().await {}
+    /// # })
+    /// ```
+    pub fn peers_changes(&self) -> PeersChangesBuilder<'_> {
+        PeersChangesBuilder {
+            session: self.session.clone(),
+            whatami: WhatAmI::Peer,
+        }
+    }
+
+    /// Return a receiver of the [`PeerEvent`]s notifying when zenoh routers connect to
+    /// or disconnect from this process.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let events = session.info().routers_changes().res().await;
+    /// while let Ok(event) = events.recv_async().await {}
+    /// # })
+    /// ```
+    pub fn routers_changes(&self) -> PeersChangesBuilder<'_> {
+        PeersChangesBuilder {
+            session: self.session.clone(),
+            whatami: WhatAmI::Router,
+        }
+    }
+
+    /// Wait until this process is connected to at least `n` zenoh peers, failing if it's not
+    /// the case once the given `timeout` expired (e.g. waiting forever with [`Duration::MAX`]).
+    ///
+    /// The connected peers are counted from the [`PeerEvent`]s of
+    /// [`peers_changes()`](SessionInfo::peers_changes), starting from the
+    /// [`peers_zid()`](SessionInfo::peers_zid), so that no polling is involved.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async_std::task::block_on(async {
+    /// use std::time::Duration;
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// session.info().wait_for_peers(2, Duration::from_secs(10)).res().await.unwrap();
+    /// # })
+    /// ```
+    pub fn wait_for_peers(&self, n: usize, timeout: Duration) -> WaitForPeersBuilder<'_> {
+        WaitForPeersBuilder {
+            session: self.session.clone(),
+            n,
+            timeout,
+        }
+    }
+
+    /// Return the [`WhatAmI`] of the zenoh node with the given [`ZenohId`],
+    /// or `None` if this process is not currently connected to it.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let mut peers_zid = session.info().peers_zid().res().await;
+    /// while let Some(peer_zid) = peers_zid.next() {
+    ///     let whatami = session.info().whatami_of(peer_zid).res().await;
+    /// }
+    /// # })
+    /// ```
+    pub fn whatami_of(&self, zid: ZenohId) -> WhatAmIOfBuilder<'_> {
+        WhatAmIOfBuilder {
+            session: self.session.clone(),
+            zid,
+        }
+    }
+
+    /// Return the [`LinkInfo`] of all the links of the transports this process
+    /// currently has with other zenoh nodes.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let links = session.info().links().res().await;
+    /// # })
+    /// ```
+    pub fn links(&self) -> LinksBuilder<'_> {
+        LinksBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the [`PeerDetail`] of all the zenoh nodes (routers, peers and clients)
+    /// this process is currently connected to, including how long each connection has lasted.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let peers = session.info().peers_detailed().res().await;
+    /// # })
+    /// ```
+    pub fn peers_detailed(&self) -> PeersDetailedBuilder<'_> {
+        PeersDetailedBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the [`ZenohId`] of the zenoh nodes this process is currently connected to,
+    /// with the protocol of each link (e.g. `tcp`, `tls` or `quic`). A node connected through
+    /// several links is listed once per link.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let protocols = session.info().peer_protocols().res().await;
+    /// # })
+    /// ```
+    pub fn peer_protocols(&self) -> PeerProtocolsBuilder<'_> {
+        PeerProtocolsBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the [`Declarations`] of subscribers, queryables and publishers
+    /// currently declared by this process through this [`Session`](crate::Session).
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let before = session.info().declarations().res().await;
+    /// let subscriber = session.declare_subscriber("key/expression").res().await.unwrap();
+    /// subscriber.undeclare().res().await.unwrap();
+    /// assert_eq!(session.info().declarations().res().await, before);
+    /// # })
+    /// ```
+    pub fn declarations(&self) -> DeclarationsBuilder<'_> {
+        DeclarationsBuilder {
+            session: self.session.clone(),
+        }
+    }
+
+    /// Return the number of subscribers matching the given key expression, i.e. that would
+    /// receive a publication of this process on it.
+    ///
+    /// The subscribers declared through this [`Session`](crate::Session) are counted one by one,
+    /// while the subscribers of other zenoh nodes are counted as known from the routing tables:
+    /// once per node, as a publication reaches it once. E.g. a zenoh node subscribed to both
+    /// `a/b` and `a/*` counts once for `a/b`.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// if session.info().matching_subscribers("key/expression").res().await.unwrap() > 0 {
+    ///     session.put("key/expression", "value").res().await.unwrap();
+    /// }
+    /// # })
+    /// ```
+    pub fn matching_subscribers<'b, TryIntoKeyExpr>(
+        &self,
+        key_expr: TryIntoKeyExpr,
+    ) -> MatchingSubscribersBuilder<'_, 'b>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        MatchingSubscribersBuilder {
+            session: self.session.clone(),
+            key_expr: key_expr.try_into().map_err(Into::into),
+        }
+    }
+
+    /// Return the [`ZenohId`] of the other zenoh nodes that declared subscribers or queryables
+    /// matching the given key expression, as known from the routing tables of this process.
+    ///
+    /// The nodes are those this process is directly connected to, as well as the nodes
+    /// whose declarations are propagated through the routers and peers it is connected to.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let peers_zid = session.info().peers_interested_in("key/**").res().await.unwrap();
+    /// for peer_zid in peers_zid {
+    ///     println!("{} is interested in key/**", peer_zid);
+    /// }
+    /// # })
+    /// ```
+    pub fn peers_interested_in<'b, TryIntoKeyExpr>(
+        &self,
+        key_expr: TryIntoKeyExpr,
+    ) -> PeersInterestedInBuilder<'_, 'b>
+    where
+        TryIntoKeyExpr: TryInto<KeyExpr<'b>>,
+        <TryIntoKeyExpr as TryInto<KeyExpr<'b>>>::Error: Into<zenoh_result::Error>,
+    {
+        PeersInterestedInBuilder {
+            session: self.session.clone(),
+            key_expr: key_expr.try_into().map_err(Into::into),
+        }
+    }
+
+    /// Return the [`LinkStats`] of all the transports this process currently has with other zenoh nodes.
+    ///
+    /// The counters of each transport are atomics, read without locking them. Listing the
+    /// transports briefly takes the lock of the transport manager, as [`links()`](SessionInfo::links)
+    /// does.
+    ///
+    /// This API is only available when zenoh is built with the `stats` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// # async_std::task::block_on(async {
+    /// use zenoh::prelude::r#async::*;
+    ///
+    /// let session = zenoh::open(config::peer()).res().await.unwrap();
+    /// let stats = session.info().link_stats().res().await;
+    /// # })
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn link_stats(&self) -> LinkStatsBuilder<'_> {
+        LinkStatsBuilder {
+            session: self.session.clone(),
+        }
+    }
 }
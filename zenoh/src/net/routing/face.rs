@@ -21,7 +21,7 @@ use zenoh_protocol::{
         Channel, CongestionControl, ConsolidationMode, QueryTarget, QueryableInfo, SubInfo,
         WhatAmI, WireExpr, ZInt, ZenohId,
     },
-    zenoh::{DataInfo, QueryBody, RoutingContext},
+    zenoh::{zmsg, DataInfo, QueryBody, RoutingContext},
 };
 use zenoh_transport::{Primitives, TransportMulticast};
 
@@ -435,6 +435,27 @@ impl Primitives for Face {
         key_expr: WireExpr,
         info: Option<DataInfo>,
         payload: ZBuf,
+    ) {
+        self.send_reply_data_with_qos(
+            qid,
+            replier_id,
+            key_expr,
+            info,
+            payload,
+            zmsg::default_channel::REPLY,
+            zmsg::default_congestion_control::REPLY,
+        );
+    }
+
+    fn send_reply_data_with_qos(
+        &self,
+        qid: ZInt,
+        replier_id: ZenohId,
+        key_expr: WireExpr,
+        info: Option<DataInfo>,
+        payload: ZBuf,
+        channel: Channel,
+        congestion_control: CongestionControl,
     ) {
         route_send_reply_data(
             &self.tables,
@@ -444,6 +465,8 @@ impl Primitives for Face {
             key_expr,
             info,
             payload,
+            channel,
+            congestion_control,
         );
     }
 
@@ -1598,6 +1598,23 @@ fn should_route(
     false
 }
 
+fn resource_subscribers(res: &Resource) -> Vec<ZenohId> {
+    let ctx = res.context();
+    res.session_ctxs
+        .values()
+        .filter(|ctx| ctx.subs.is_some())
+        .map(|ctx| ctx.face.zid)
+        .chain(ctx.router_subs.iter().copied())
+        .chain(ctx.peer_subs.iter().copied())
+        .collect()
+}
+
+/// Returns the [`ZenohId`] of the other zenoh nodes that declared subscriptions
+/// on resources matching the given key expression.
+pub(crate) fn remote_subscribers(tables: &Tables, key_expr: &keyexpr) -> HashSet<ZenohId> {
+    Resource::get_remote_declarations(tables, key_expr, resource_subscribers)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn full_reentrant_route_data(
     tables_ref: &RwLock<Tables>,
@@ -21,7 +21,7 @@ use async_trait::async_trait;
 use ordered_float::OrderedFloat;
 use petgraph::graph::NodeIndex;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::sync::{Arc, RwLockReadGuard, Weak};
 use zenoh_buffers::ZBuf;
@@ -32,7 +32,8 @@ use zenoh_protocol::{
             include::{Includer, DEFAULT_INCLUDER},
             OwnedKeyExpr,
         },
-        ConsolidationMode, QueryTarget, QueryableInfo, WhatAmI, WireExpr, ZInt, ZenohId,
+        Channel, CongestionControl, ConsolidationMode, QueryTarget, QueryableInfo, WhatAmI,
+        WireExpr, ZInt, ZenohId,
     },
     zenoh::{DataInfo, QueryBody, RoutingContext},
 };
@@ -2006,17 +2007,21 @@ pub(crate) fn route_send_reply_data(
     key_expr: WireExpr,
     info: Option<DataInfo>,
     payload: ZBuf,
+    channel: Channel,
+    congestion_control: CongestionControl,
 ) {
     let queries_lock = zread!(tables_ref.queries_lock);
     match face.pending_queries.get(&qid) {
         Some(query) => {
             drop(queries_lock);
-            query.src_face.primitives.clone().send_reply_data(
+            query.src_face.primitives.clone().send_reply_data_with_qos(
                 query.src_qid,
                 replier_id,
                 key_expr,
                 info,
                 payload,
+                channel,
+                congestion_control,
             );
         }
         None => log::warn!(
@@ -2072,3 +2077,18 @@ pub(crate) fn finalize_pending_query(query: Arc<Query>) {
             .send_reply_final(query.src_qid);
     }
 }
+
+/// Returns the [`ZenohId`] of the other zenoh nodes that declared queryables
+/// on resources matching the given key expression.
+pub(crate) fn remote_queryables(tables: &Tables, key_expr: &keyexpr) -> HashSet<ZenohId> {
+    Resource::get_remote_declarations(tables, key_expr, |res| {
+        let ctx = res.context();
+        res.session_ctxs
+            .values()
+            .filter(|ctx| ctx.qabl.is_some())
+            .map(|ctx| ctx.face.zid)
+            .chain(ctx.router_qabls.keys().copied())
+            .chain(ctx.peer_qabls.keys().copied())
+            .collect()
+    })
+}
@@ -630,6 +630,27 @@ impl Resource {
         matches
     }
 
+    /// Returns the [`ZenohId`] of the other zenoh nodes that made declarations on the resources
+    /// matching the given key expression, `declarers` listing the nodes that made a declaration
+    /// on a resource.
+    pub(crate) fn get_remote_declarations<F>(
+        tables: &Tables,
+        key_expr: &keyexpr,
+        declarers: F,
+    ) -> HashSet<ZenohId>
+    where
+        F: Fn(&Resource) -> Vec<ZenohId>,
+    {
+        let mut declarations = HashSet::new();
+        for res in Resource::get_matches(tables, key_expr)
+            .iter()
+            .filter_map(|res| res.upgrade())
+        {
+            declarations.extend(declarers(&res).into_iter().filter(|zid| *zid != tables.zid));
+        }
+        declarations
+    }
+
     pub fn match_resource(_tables: &Tables, res: &mut Arc<Resource>, matches: Vec<Weak<Resource>>) {
         if res.context.is_some() {
             for match_ in &matches {
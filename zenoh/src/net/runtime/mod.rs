@@ -25,6 +25,7 @@ use super::routing::face::Face;
 use super::routing::pubsub::full_reentrant_route_data;
 use super::routing::router::{LinkStateInterceptor, Router};
 use crate::config::{unwrap_or_default, Config, ModeDependent, Notifier};
+use crate::info::PeerEvent;
 use crate::GIT_VERSION;
 pub use adminspace::AdminSpace;
 use async_std::task::JoinHandle;
@@ -56,6 +57,7 @@ pub struct RuntimeState {
     pub config: Notifier<Config>,
     pub manager: TransportManager,
     pub transport_handlers: std::sync::RwLock<Vec<Arc<dyn TransportEventHandler>>>,
+    pub(crate) peer_events: std::sync::RwLock<Vec<(WhatAmI, flume::Sender<PeerEvent>)>>,
     pub(crate) locators: std::sync::RwLock<Vec<Locator>>,
     pub hlc: Option<Arc<HLC>>,
     pub(crate) stop_source: std::sync::RwLock<Option<StopSource>>,
@@ -146,6 +148,7 @@ impl Runtime {
                 config: config.clone(),
                 manager: transport_manager,
                 transport_handlers: std::sync::RwLock::new(vec![]),
+                peer_events: std::sync::RwLock::new(vec![]),
                 locators: std::sync::RwLock::new(vec![]),
                 hlc,
                 stop_source: std::sync::RwLock::new(Some(StopSource::new())),
@@ -189,6 +192,20 @@ impl Runtime {
         zwrite!(self.state.transport_handlers).push(handler);
     }
 
+    pub(crate) fn new_peer_events_listener(
+        &self,
+        whatami: WhatAmI,
+        sender: flume::Sender<PeerEvent>,
+    ) {
+        zwrite!(self.state.peer_events).push((whatami, sender));
+    }
+
+    // Notify the listeners of the given WhatAmI, removing the ones that have been dropped.
+    pub(crate) fn notify_peer_event(&self, whatami: WhatAmI, event: PeerEvent) {
+        zwrite!(self.state.peer_events)
+            .retain(|(w, sender)| *w != whatami || sender.send(event).is_ok());
+    }
+
     pub async fn close(&self) -> ZResult<()> {
         log::trace!("Runtime::close())");
         drop(self.stop_source.write().unwrap().take());
@@ -236,12 +253,16 @@ impl TransportEventHandler for RuntimeTransportEventHandler {
                             handler.new_unicast(peer.clone(), transport.clone()).ok()
                         })
                         .collect();
-                Ok(Arc::new(RuntimeSession {
+                let session = Arc::new(RuntimeSession {
                     runtime: runtime.clone(),
+                    zid: peer.zid,
+                    whatami: peer.whatami,
                     endpoint: std::sync::RwLock::new(None),
                     main_handler: runtime.router.new_transport_unicast(transport).unwrap(),
                     slave_handlers,
-                }))
+                });
+                runtime.notify_peer_event(peer.whatami, PeerEvent::Joined(peer.zid));
+                Ok(session)
             }
             None => bail!("Runtime not yet ready!"),
         }
@@ -272,6 +293,8 @@ impl TransportEventHandler for RuntimeTransportEventHandler {
 
 pub(super) struct RuntimeSession {
     pub(super) runtime: Runtime,
+    pub(super) zid: ZenohId,
+    pub(super) whatami: WhatAmI,
     pub(super) endpoint: std::sync::RwLock<Option<EndPoint>>,
     pub(super) main_handler: Arc<LinkStateInterceptor>,
     pub(super) slave_handlers: Vec<Arc<dyn TransportPeerEventHandler>>,
@@ -329,6 +352,8 @@ impl TransportPeerEventHandler for RuntimeSession {
         for handler in &self.slave_handlers {
             handler.closed();
         }
+        self.runtime
+            .notify_peer_event(self.whatami, PeerEvent::Left(self.zid));
     }
 
     fn as_any(&self) -> &dyn Any {
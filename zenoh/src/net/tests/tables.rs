@@ -176,6 +176,85 @@ fn match_test() {
     }
 }
 
+#[test]
+fn remote_subscriptions_test() {
+    let tables = TablesLock {
+        tables: RwLock::new(Tables::new(
+            ZenohId::try_from([1]).unwrap(),
+            WhatAmI::Client,
+            Some(Arc::new(HLC::default())),
+            false,
+            true,
+            Duration::from_millis(ZN_QUERIES_DEFAULT_TIMEOUT_DEFAULT.parse().unwrap()),
+        )),
+        ctrl_lock: Mutex::new(()),
+        queries_lock: RwLock::new(()),
+    };
+
+    let sub_info = SubInfo {
+        reliability: Reliability::Reliable,
+        mode: SubMode::Push,
+    };
+
+    // face0 has overlapping subscriptions, face1 is the local node itself
+    let face0 = zwrite!(tables.tables).open_face(
+        ZenohId::try_from([2]).unwrap(),
+        WhatAmI::Client,
+        Arc::new(DummyPrimitives::new()),
+    );
+    let face1 = zwrite!(tables.tables).open_face(
+        ZenohId::try_from([1]).unwrap(),
+        WhatAmI::Client,
+        Arc::new(DummyPrimitives::new()),
+    );
+    let face2 = zwrite!(tables.tables).open_face(
+        ZenohId::try_from([3]).unwrap(),
+        WhatAmI::Client,
+        Arc::new(DummyPrimitives::new()),
+    );
+    for (face, expr) in [
+        (&face0, "test/remote/a"),
+        (&face0, "test/remote/*"),
+        (&face1, "test/remote/a"),
+        (&face2, "test/remote/b"),
+    ] {
+        declare_client_subscription(
+            &tables,
+            zread!(tables.tables),
+            &mut face.upgrade().unwrap(),
+            &expr.into(),
+            &sub_info,
+        );
+    }
+
+    let rtables = zread!(tables.tables);
+    let zids = |expr: &str| {
+        let mut zids: Vec<ZenohId> = remote_subscribers(&rtables, keyexpr::new(expr).unwrap())
+            .into_iter()
+            .collect();
+        zids.sort_unstable();
+        zids
+    };
+    // each remote node is listed once, even with overlapping subscriptions,
+    // the subscriptions of the local node not being listed
+    assert_eq!(
+        zids("test/remote/**"),
+        [
+            ZenohId::try_from([2]).unwrap(),
+            ZenohId::try_from([3]).unwrap()
+        ]
+    );
+    assert_eq!(zids("test/remote/a"), [ZenohId::try_from([2]).unwrap()]);
+    assert_eq!(
+        zids("test/remote/b"),
+        [
+            ZenohId::try_from([2]).unwrap(),
+            ZenohId::try_from([3]).unwrap()
+        ]
+    );
+    assert_eq!(zids("test/other"), []);
+}
+
 #[test]
 fn clean_test() {
     let tables = TablesLock {
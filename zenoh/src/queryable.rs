@@ -26,7 +26,8 @@ use std::future::Ready;
 use std::ops::Deref;
 use std::sync::Arc;
 use zenoh_core::{AsyncResolve, Resolvable, SyncResolve};
-use zenoh_protocol::core::WireExpr;
+use zenoh_protocol::core::{Channel, CongestionControl, WireExpr};
+use zenoh_protocol::zenoh::zmsg;
 use zenoh_result::ZResult;
 use zenoh_transport::Primitives;
 
@@ -93,6 +94,8 @@ impl Query {
         ReplyBuilder {
             query: self,
             result,
+            priority: Priority::default(),
+            congestion_control: zmsg::default_congestion_control::REPLY,
         }
     }
 
@@ -140,6 +143,24 @@ impl fmt::Display for Query {
 pub struct ReplyBuilder<'a> {
     query: &'a Query,
     result: Result<Sample, Value>,
+    priority: Priority,
+    congestion_control: CongestionControl,
+}
+
+impl ReplyBuilder<'_> {
+    /// Change the priority of the reply.
+    #[inline]
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Change the `congestion_control` to apply when routing the reply.
+    #[inline]
+    pub fn congestion_control(mut self, congestion_control: CongestionControl) -> Self {
+        self.congestion_control = congestion_control;
+        self
+    }
 }
 
 impl<'a> Resolvable for ReplyBuilder<'a> {
@@ -156,7 +177,7 @@ impl SyncResolve for ReplyBuilder<'_> {
                     bail!("Attempted to reply on `{}`, which does not intersect with query `{}`, despite query only allowing replies on matching key expressions", sample.key_expr, self.query.key_expr())
                 }
                 let (key_expr, payload, data_info) = sample.split();
-                self.query.inner.primitives.send_reply_data(
+                self.query.inner.primitives.send_reply_data_with_qos(
                     self.query.inner.qid,
                     self.query.inner.zid,
                     WireExpr {
@@ -165,6 +186,11 @@ impl SyncResolve for ReplyBuilder<'_> {
                     },
                     Some(data_info),
                     payload,
+                    Channel {
+                        priority: self.priority.into(),
+                        reliability: zmsg::default_channel::REPLY.reliability,
+                    },
+                    self.congestion_control,
                 );
                 Ok(())
             }
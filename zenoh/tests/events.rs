@@ -14,6 +14,7 @@
 use async_std::prelude::FutureExt;
 use async_std::task;
 use std::time::Duration;
+use zenoh::info::PeerEvent;
 use zenoh::prelude::r#async::*;
 use zenoh::query::Reply;
 use zenoh_core::zasync_executor_init;
@@ -120,3 +121,58 @@ fn zenoh_events() {
         close_session(session).await;
     });
 }
+
+#[test]
+fn zenoh_peers_changes() {
+    task::block_on(async {
+        zasync_executor_init!();
+
+        let session = open_session(&["tcp/127.0.0.1:18449"], &[]).await;
+        let events = session.info().peers_changes().res().await;
+
+        let session2 = open_session(&["tcp/127.0.0.1:18450"], &["tcp/127.0.0.1:18449"]).await;
+        let zid2 = session2.zid();
+
+        let event = ztimeout!(events.recv_async()).unwrap();
+        assert_eq!(event, PeerEvent::Joined(zid2));
+
+        close_session(session2).await;
+
+        let event = ztimeout!(events.recv_async()).unwrap();
+        assert_eq!(event, PeerEvent::Left(zid2));
+
+        close_session(session).await;
+    });
+}
+
+#[test]
+fn zenoh_wait_for_peers() {
+    task::block_on(async {
+        zasync_executor_init!();
+
+        let session = open_session(&["tcp/127.0.0.1:18451"], &[]).await;
+        ztimeout!(session
+            .info()
+            .wait_for_peers(0, Duration::from_millis(100))
+            .res_async())
+        .unwrap();
+        assert!(ztimeout!(session
+            .info()
+            .wait_for_peers(1, Duration::from_millis(100))
+            .res_async())
+        .is_err());
+
+        let info = session.info();
+        let (waited, session2) = futures::join!(
+            info.wait_for_peers(1, TIMEOUT).res_async(),
+            open_session(&["tcp/127.0.0.1:18452"], &["tcp/127.0.0.1:18451"])
+        );
+        waited.unwrap();
+
+        // the already connected peers are counted, even without deadline
+        ztimeout!(session.info().wait_for_peers(1, Duration::MAX).res_async()).unwrap();
+
+        close_session(session2).await;
+        close_session(session).await;
+    });
+}